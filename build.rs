@@ -12,6 +12,36 @@ fn main() {
     // Configure and compile the proto files
     tonic_build::configure()
         .file_descriptor_set_path(descriptor_path) // Optional: for advanced usage
+        // Interactive sessions are persisted with the match they are about
+        .type_attribute(
+            "matcher.EndpointMatch",
+            "#[derive(serde::Serialize, serde::Deserialize)]",
+        )
+        .type_attribute(
+            "matcher.ParameterInfo",
+            "#[derive(serde::Serialize, serde::Deserialize)]",
+        )
+        // Match results are published as JSON by the Iggy worker
+        .type_attribute(
+            "matcher.MatchResponse",
+            "#[derive(serde::Serialize, serde::Deserialize)]",
+        )
+        .type_attribute(
+            "matcher.Intent",
+            "#[derive(serde::Serialize, serde::Deserialize)]",
+        )
+        .type_attribute(
+            "matcher.DebugInfo",
+            "#[derive(serde::Serialize, serde::Deserialize)]",
+        )
+        .type_attribute(
+            "matcher.SuppressedMatch",
+            "#[derive(serde::Serialize, serde::Deserialize)]",
+        )
+        .type_attribute(
+            "matcher.SpellCorrection",
+            "#[derive(serde::Serialize, serde::Deserialize)]",
+        )
         .compile_protos(&["proto/matcher.proto"], &["proto"])
         .unwrap_or_else(|e| panic!("Failed to compile proto files: {}", e));
 }
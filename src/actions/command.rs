@@ -0,0 +1,46 @@
+use anyhow::{anyhow, Context, Result as AnyhowResult};
+use async_trait::async_trait;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use super::payload::ActionPayload;
+use super::ActionDispatcher;
+
+/// Runs a local program with the action message as JSON on its standard
+/// input. The endpoint id is also passed in `MATCHER_ENDPOINT`.
+pub struct CommandDispatcher {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandDispatcher {
+    pub fn new(program: &str, args: &[String]) -> Self {
+        Self {
+            program: program.to_string(),
+            args: args.to_vec(),
+        }
+    }
+}
+
+#[async_trait]
+impl ActionDispatcher for CommandDispatcher {
+    async fn dispatch(&self, payload: &ActionPayload) -> AnyhowResult<()> {
+        let message = serde_json::to_vec(payload)?;
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .env("MATCHER_ENDPOINT", &payload.endpoint_id)
+            .stdin(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to start {}", self.program))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(&message).await?;
+        }
+        let status = child.wait().await?;
+        if !status.success() {
+            return Err(anyhow!("{} exited with {}", self.program, status));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+use anyhow::{anyhow, Result as AnyhowResult};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use super::payload::ActionPayload;
+use super::ActionDispatcher;
+use crate::messaging::iggy_pool::IggyPool;
+use crate::messaging::send_structured_message::send_structured_message;
+
+pub struct IggyDispatcher {
+    pool: Arc<IggyPool>,
+    stream: String,
+    topic: String,
+}
+
+impl IggyDispatcher {
+    /// Publishes to the given stream and topic, or the configured ones.
+    pub fn new(pool: &Arc<IggyPool>, stream: Option<&str>, topic: Option<&str>) -> Self {
+        Self {
+            pool: pool.clone(),
+            stream: stream.unwrap_or(&pool.config().stream).to_string(),
+            topic: topic.unwrap_or(&pool.config().topic).to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl ActionDispatcher for IggyDispatcher {
+    async fn dispatch(&self, payload: &ActionPayload) -> AnyhowResult<()> {
+        send_structured_message(&self.pool, &self.stream, &self.topic, payload)
+            .await
+            .map_err(|e| anyhow!("Message sending failed: {}", e))?;
+
+        println!(
+            "Sent notification for endpoint: {} to {}/{}",
+            payload.endpoint_id, self.stream, self.topic
+        );
+        Ok(())
+    }
+}
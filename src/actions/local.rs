@@ -0,0 +1,64 @@
+use anyhow::{Context, Result as AnyhowResult};
+use async_trait::async_trait;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use tracing::info;
+
+use super::payload::ActionPayload;
+use super::ActionDispatcher;
+
+/// Appends one JSON line per action to a file.
+pub struct FileDispatcher {
+    path: PathBuf,
+}
+
+impl FileDispatcher {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: PathBuf::from(path),
+        }
+    }
+}
+
+#[async_trait]
+impl ActionDispatcher for FileDispatcher {
+    async fn dispatch(&self, payload: &ActionPayload) -> AnyhowResult<()> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut line = serde_json::to_string(payload)?;
+        line.push('\n');
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("Failed to open {:?}", self.path))?;
+        file.write_all(line.as_bytes()).await?;
+        // tokio writes in the background, make sure the line is on disk
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+pub struct StdoutDispatcher;
+
+#[async_trait]
+impl ActionDispatcher for StdoutDispatcher {
+    async fn dispatch(&self, payload: &ActionPayload) -> AnyhowResult<()> {
+        println!("{}", serde_json::to_string(payload)?);
+        Ok(())
+    }
+}
+
+/// Matches without acting, e.g. to evaluate endpoints without a backend.
+pub struct NoopDispatcher;
+
+#[async_trait]
+impl ActionDispatcher for NoopDispatcher {
+    async fn dispatch(&self, payload: &ActionPayload) -> AnyhowResult<()> {
+        info!("No action configured for endpoint {}", payload.endpoint_id);
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+pub mod command;
+pub mod iggy;
+pub mod local;
+pub mod negation;
+pub mod outbox;
+pub mod outbox_command;
+pub mod payload;
+pub mod webhook;
+
+use anyhow::{anyhow, Result as AnyhowResult};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::info;
+
+use self::negation::apply_negation_policy;
+use self::outbox::Delivery;
+use self::payload::ActionPayload;
+use crate::config::{ActionConfig, Config};
+use crate::messaging::iggy_pool::IggyPool;
+
+/// A matched endpoint whose action has to be carried out.
+#[derive(Debug, Clone)]
+pub struct Action {
+    pub endpoint_id: String,
+    pub parameters: HashMap<String, String>,
+    pub similarity: f32,
+    pub negated: bool,
+    pub query: Option<String>,
+    pub language: Option<String>,
+    pub session_id: Option<String>,
+    // Idempotency key of the dispatch, a new one is generated when None
+    pub request_id: Option<String>,
+}
+
+#[async_trait]
+pub trait ActionDispatcher: Send + Sync {
+    async fn dispatch(&self, payload: &ActionPayload) -> AnyhowResult<()>;
+}
+
+pub fn create_dispatcher(
+    config: &ActionConfig,
+    iggy_pool: &Arc<IggyPool>,
+    http_client: &reqwest::Client,
+) -> Box<dyn ActionDispatcher> {
+    match config {
+        ActionConfig::Iggy { stream, topic } => Box::new(iggy::IggyDispatcher::new(
+            iggy_pool,
+            stream.as_deref(),
+            topic.as_deref(),
+        )),
+        ActionConfig::Webhook {
+            url,
+            headers,
+            timeout_secs,
+        } => Box::new(webhook::WebhookDispatcher::new(
+            http_client,
+            url,
+            headers,
+            *timeout_secs,
+        )),
+        ActionConfig::Command { program, args } => {
+            Box::new(command::CommandDispatcher::new(program, args))
+        }
+        ActionConfig::File { path } => Box::new(local::FileDispatcher::new(path)),
+        ActionConfig::Stdout => Box::new(local::StdoutDispatcher),
+        ActionConfig::Noop => Box::new(local::NoopDispatcher),
+    }
+}
+
+/// Whether any endpoint dispatches to Iggy, so the broker is needed.
+pub fn uses_iggy(config: &Config) -> bool {
+    std::iter::once(&config.actions.default)
+        .chain(config.endpoints.iter().filter_map(|e| e.action.as_ref()))
+        .any(|action| matches!(action, ActionConfig::Iggy { .. }))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DispatchOutcome {
+    // `endpoint_id` differs from the matched one when an inverse was dispatched
+    Dispatched { endpoint_id: String, negated: bool },
+    // The first attempt failed, the outbox retries it in the background
+    Queued { endpoint_id: String, error: String },
+    // A dispatch with the same request id was already recorded
+    Duplicate { endpoint_id: String },
+    // Negated query on an endpoint whose policy is to suppress
+    Suppressed,
+}
+
+/// Sends the action to the backend configured for its endpoint, once the
+/// endpoint's negation policy has been applied. With the outbox enabled the
+/// message is recorded before the first attempt.
+pub async fn dispatch_action(action: &Action, config: &Config) -> AnyhowResult<DispatchOutcome> {
+    let Some(action) = apply_negation_policy(action, config) else {
+        info!(
+            "Negated query, action for endpoint {} suppressed",
+            action.endpoint_id
+        );
+        return Ok(DispatchOutcome::Suppressed);
+    };
+
+    let endpoint = config.endpoints.iter().find(|e| e.id == action.endpoint_id);
+    info!(
+        "Dispatching action for endpoint {} with similarity {}",
+        action.endpoint_id, action.similarity
+    );
+    let payload = ActionPayload::new(&action, endpoint);
+    let endpoint_id = action.endpoint_id;
+
+    if !config.actions.outbox.enabled {
+        send_payload(&payload, config).await?;
+        return Ok(DispatchOutcome::Dispatched {
+            endpoint_id,
+            negated: action.negated,
+        });
+    }
+
+    let outbox = config.outbox();
+    let Some(id) = outbox.enqueue(payload).await? else {
+        return Ok(DispatchOutcome::Duplicate { endpoint_id });
+    };
+    match outbox.deliver(&id, config).await? {
+        Some(Delivery::Sent) | None => Ok(DispatchOutcome::Dispatched {
+            endpoint_id,
+            negated: action.negated,
+        }),
+        Some(Delivery::Retrying { error, .. }) => {
+            Ok(DispatchOutcome::Queued { endpoint_id, error })
+        }
+        Some(Delivery::DeadLettered { error }) => Err(anyhow!(error)),
+    }
+}
+
+/// Sends an already built payload through the dispatcher of its endpoint.
+pub(crate) async fn send_payload(payload: &ActionPayload, config: &Config) -> AnyhowResult<()> {
+    let endpoint = config
+        .endpoints
+        .iter()
+        .find(|e| e.id == payload.endpoint_id);
+    create_dispatcher(
+        config.actions.for_endpoint(endpoint),
+        config.iggy_pool(),
+        config.http_client(),
+    )
+    .dispatch(payload)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_endpoint_action_overrides_default() {
+        let path =
+            std::env::temp_dir().join(format!("matcher-actions-{}.jsonl", uuid::Uuid::new_v4()));
+        let config: Config = serde_yaml::from_str(&format!(
+            r#"
+endpoints:
+  - id: "send_email"
+    text: "envoyer un email"
+    description: "Envoyer un email"
+    action:
+      type: "file"
+      path: "{}"
+actions:
+  default:
+    type: "noop"
+  outbox:
+    enabled: false
+"#,
+            path.display()
+        ))
+        .unwrap();
+
+        for endpoint_id in ["send_email", "unknown"] {
+            let action = Action {
+                endpoint_id: endpoint_id.to_string(),
+                parameters: HashMap::from([("email".to_string(), "toto@gmail.com".to_string())]),
+                similarity: 0.9,
+                negated: false,
+                query: None,
+                language: None,
+                session_id: None,
+                request_id: None,
+            };
+            dispatch_action(&action, &config).await.unwrap();
+        }
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written.lines().count(), 1);
+        assert!(written.contains("\"endpoint_id\":\"send_email\""));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,78 @@
+use super::Action;
+use crate::config::{Config, NegationPolicy};
+
+/// Applies the negation policy of the matched endpoint: returns the action
+/// to dispatch, or None when a negated query must not trigger anything.
+pub fn apply_negation_policy(action: &Action, config: &Config) -> Option<Action> {
+    if !action.negated {
+        return Some(action.clone());
+    }
+
+    let policy = config
+        .endpoints
+        .iter()
+        .find(|e| e.id == action.endpoint_id)
+        .map(|e| e.negation.clone())
+        .unwrap_or_default();
+    match policy {
+        NegationPolicy::Suppress => None,
+        // The inverse endpoint carries the meaning, it is not negated itself
+        NegationPolicy::Inverse { endpoint } => Some(Action {
+            endpoint_id: endpoint,
+            negated: false,
+            ..action.clone()
+        }),
+        NegationPolicy::Dispatch => Some(action.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_negation_policies() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+endpoints:
+  - id: "send_email"
+    text: "envoyer un email"
+    description: "Envoyer un email"
+  - id: "start_app"
+    text: "démarrer l'application"
+    description: "Démarrer l'application"
+    negation:
+      policy: "inverse"
+      endpoint: "stop_app"
+  - id: "log_event"
+    text: "journaliser"
+    description: "Journaliser"
+    negation:
+      policy: "dispatch"
+"#,
+        )
+        .unwrap();
+        let action = |endpoint_id: &str, negated: bool| Action {
+            endpoint_id: endpoint_id.to_string(),
+            parameters: HashMap::new(),
+            similarity: 0.9,
+            negated,
+            query: None,
+            language: None,
+            session_id: None,
+            request_id: None,
+        };
+
+        assert!(apply_negation_policy(&action("send_email", true), &config).is_none());
+        assert!(apply_negation_policy(&action("send_email", false), &config).is_some());
+
+        let inverse = apply_negation_policy(&action("start_app", true), &config).unwrap();
+        assert_eq!(inverse.endpoint_id, "stop_app");
+        assert!(!inverse.negated);
+
+        let flagged = apply_negation_policy(&action("log_event", true), &config).unwrap();
+        assert_eq!(flagged.endpoint_id, "log_event");
+        assert!(flagged.negated);
+    }
+}
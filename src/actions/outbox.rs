@@ -0,0 +1,465 @@
+use anyhow::{anyhow, Context, Result as AnyhowResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use super::payload::ActionPayload;
+use super::send_payload;
+use crate::config::{Config, OutboxConfig};
+
+/// A dispatch recorded on disk. Its id is the payload's `request_id`, which
+/// doubles as the idempotency key while the entry is kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub payload: ActionPayload,
+    pub attempts: u32,
+    // Unix timestamps (seconds)
+    pub created_at: u64,
+    pub next_attempt_at: u64,
+    pub last_error: Option<String>,
+    #[serde(default)]
+    pub sent_at: Option<u64>,
+}
+
+impl OutboxEntry {
+    pub fn id(&self) -> &str {
+        &self.payload.request_id
+    }
+
+    // Whether a delivered entry is older than `retention_secs`; 0 keeps
+    // entries forever
+    fn expired(&self, retention_secs: u64) -> bool {
+        let sent_at = self.sent_at.unwrap_or(self.created_at);
+        retention_secs > 0 && now().saturating_sub(sent_at) >= retention_secs
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutboxQueue {
+    Pending,
+    Sent,
+    Dead,
+}
+
+impl OutboxQueue {
+    fn directory(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Sent => "sent",
+            Self::Dead => "dead",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Delivery {
+    Sent,
+    Retrying { attempts: u32, error: String },
+    DeadLettered { error: String },
+}
+
+/// File-backed queue under `actions.outbox.path`: one JSON file per message
+/// in `pending/`, moved to `sent/` once delivered or to `dead/` after
+/// `max_attempts` failures. `sent/` entries are purged after
+/// `sent_retention_secs`.
+pub struct Outbox {
+    config: OutboxConfig,
+    root: PathBuf,
+    // Messages being delivered by this process, never sent twice at once
+    in_flight: Mutex<HashSet<String>>,
+}
+
+impl std::fmt::Debug for Outbox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Outbox").field("root", &self.root).finish()
+    }
+}
+
+impl Outbox {
+    pub fn new(config: &OutboxConfig) -> Self {
+        Self {
+            config: config.clone(),
+            root: PathBuf::from(&config.path),
+            in_flight: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Records the payload, returns its id, or None if a message with the
+    /// same idempotency key is pending, dead-lettered, or was sent within
+    /// `sent_retention_secs`.
+    pub async fn enqueue(&self, payload: ActionPayload) -> AnyhowResult<Option<String>> {
+        let id = payload.request_id.clone();
+        let entry = OutboxEntry {
+            payload,
+            attempts: 0,
+            created_at: now(),
+            next_attempt_at: now(),
+            last_error: None,
+            sent_at: None,
+        };
+
+        // Creating the pending file fails if it exists, so two concurrent
+        // enqueues of the same id cannot both succeed
+        let path = self.entry_path(OutboxQueue::Pending, &id)?;
+        tokio::fs::create_dir_all(self.root.join(OutboxQueue::Pending.directory())).await?;
+        let mut file = match tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                info!("Outbox already holds message {}, skipping", id);
+                return Ok(None);
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to create {:?}", path)),
+        };
+        file.write_all(&serde_json::to_vec_pretty(&entry)?).await?;
+        file.sync_all().await?;
+
+        // Checked after the pending file exists: a delivery writes sent/
+        // before removing pending/, so it cannot slip in between
+        for queue in [OutboxQueue::Sent, OutboxQueue::Dead] {
+            let Some(recorded) = self.read(queue, &id).await? else {
+                continue;
+            };
+            if queue == OutboxQueue::Sent && recorded.expired(self.config.sent_retention_secs) {
+                self.remove(queue, &id).await?;
+                continue;
+            }
+            info!("Outbox already holds message {}, skipping", id);
+            tokio::fs::remove_file(&path).await?;
+            return Ok(None);
+        }
+        Ok(Some(id))
+    }
+
+    /// Sends a pending message once. Returns None when it is no longer
+    /// pending or already being delivered.
+    pub async fn deliver(&self, id: &str, config: &Config) -> AnyhowResult<Option<Delivery>> {
+        if !self.in_flight.lock().await.insert(id.to_string()) {
+            return Ok(None);
+        }
+        let delivery = self.deliver_pending(id, config).await;
+        self.in_flight.lock().await.remove(id);
+        delivery
+    }
+
+    async fn deliver_pending(&self, id: &str, config: &Config) -> AnyhowResult<Option<Delivery>> {
+        let Some(mut entry) = self.read(OutboxQueue::Pending, id).await? else {
+            return Ok(None);
+        };
+
+        entry.attempts += 1;
+        let delivery = match send_payload(&entry.payload, config).await {
+            Ok(()) => {
+                entry.last_error = None;
+                entry.sent_at = Some(now());
+                self.move_entry(&entry, OutboxQueue::Sent).await?;
+                Delivery::Sent
+            }
+            Err(e) if entry.attempts >= self.config.max_attempts => {
+                warn!(
+                    "Message {} failed {} times, moved to dead letters: {}",
+                    id, entry.attempts, e
+                );
+                entry.last_error = Some(e.to_string());
+                self.move_entry(&entry, OutboxQueue::Dead).await?;
+                Delivery::DeadLettered {
+                    error: e.to_string(),
+                }
+            }
+            Err(e) => {
+                entry.last_error = Some(e.to_string());
+                entry.next_attempt_at = now() + self.backoff_secs(entry.attempts);
+                self.write(OutboxQueue::Pending, &entry).await?;
+                Delivery::Retrying {
+                    attempts: entry.attempts,
+                    error: e.to_string(),
+                }
+            }
+        };
+        Ok(Some(delivery))
+    }
+
+    /// Retries every pending message whose backoff has elapsed.
+    pub async fn process_due(&self, config: &Config) -> AnyhowResult<usize> {
+        let mut delivered = 0;
+        for entry in self.list(OutboxQueue::Pending).await? {
+            if entry.next_attempt_at > now() {
+                continue;
+            }
+            match self.deliver(entry.id(), config).await {
+                Ok(Some(Delivery::Sent)) => delivered += 1,
+                Ok(_) => {}
+                // One unreadable or unmovable message must not block the others
+                Err(e) => warn!("Failed to deliver outbox message {}: {}", entry.id(), e),
+            }
+        }
+        Ok(delivered)
+    }
+
+    /// Moves dead letters back to pending with a fresh attempt count and sends
+    /// them again: the one with `id`, or all of them.
+    pub async fn replay(
+        &self,
+        id: Option<&str>,
+        config: &Config,
+    ) -> AnyhowResult<Vec<(String, Delivery)>> {
+        let mut replayed = Vec::new();
+        for mut entry in self.list(OutboxQueue::Dead).await? {
+            if id.is_some_and(|id| id != entry.id()) {
+                continue;
+            }
+            entry.attempts = 0;
+            entry.next_attempt_at = now();
+            self.move_entry(&entry, OutboxQueue::Pending).await?;
+            if let Some(delivery) = self.deliver(entry.id(), config).await? {
+                replayed.push((entry.id().to_string(), delivery));
+            }
+        }
+        if let (Some(id), true) = (id, replayed.is_empty()) {
+            return Err(anyhow!("No dead letter with id {}", id));
+        }
+        Ok(replayed)
+    }
+
+    /// Deletes the sent messages older than `retention_secs`, or than
+    /// `sent_retention_secs` by default. Returns how many were deleted.
+    pub async fn purge(&self, retention_secs: Option<u64>) -> AnyhowResult<usize> {
+        let retention_secs = retention_secs.unwrap_or(self.config.sent_retention_secs);
+        let mut purged = 0;
+        for entry in self.list(OutboxQueue::Sent).await? {
+            if entry.expired(retention_secs) {
+                self.remove(OutboxQueue::Sent, entry.id()).await?;
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+
+    /// Entries of a queue, oldest first.
+    pub async fn list(&self, queue: OutboxQueue) -> AnyhowResult<Vec<OutboxEntry>> {
+        let directory = self.root.join(queue.directory());
+        let mut files = match tokio::fs::read_dir(&directory).await {
+            Ok(files) => files,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut entries = Vec::new();
+        while let Some(file) = files.next_entry().await? {
+            let path = file.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match tokio::fs::read_to_string(&path).await {
+                Ok(content) => match serde_json::from_str::<OutboxEntry>(&content) {
+                    Ok(entry) => entries.push(entry),
+                    Err(e) => warn!("Invalid outbox file {:?}: {}", path, e),
+                },
+                Err(e) => warn!("Failed to read outbox file {:?}: {}", path, e),
+            }
+        }
+        entries.sort_by_key(|entry| entry.created_at);
+        Ok(entries)
+    }
+
+    // Exponential backoff, doubling from initial_backoff_secs up to max_backoff_secs
+    fn backoff_secs(&self, attempts: u32) -> u64 {
+        let factor = 2u64.saturating_pow(attempts.saturating_sub(1));
+        self.config
+            .initial_backoff_secs
+            .saturating_mul(factor)
+            .min(self.config.max_backoff_secs)
+    }
+
+    fn entry_path(&self, queue: OutboxQueue, id: &str) -> AnyhowResult<PathBuf> {
+        // Ids end up in file names, keep them inside the outbox
+        if id.is_empty()
+            || !id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(anyhow!("Invalid outbox message id '{}'", id));
+        }
+        Ok(self
+            .root
+            .join(queue.directory())
+            .join(format!("{}.json", id)))
+    }
+
+    async fn read(&self, queue: OutboxQueue, id: &str) -> AnyhowResult<Option<OutboxEntry>> {
+        let path = self.entry_path(queue, id)?;
+        match tokio::fs::read_to_string(&path).await {
+            Ok(content) => {
+                Ok(Some(serde_json::from_str(&content).with_context(|| {
+                    format!("Invalid outbox file {:?}", path)
+                })?))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {:?}", path)),
+        }
+    }
+
+    async fn write(&self, queue: OutboxQueue, entry: &OutboxEntry) -> AnyhowResult<()> {
+        let path = self.entry_path(queue, entry.id())?;
+        tokio::fs::create_dir_all(self.root.join(queue.directory())).await?;
+
+        // Write then rename so that a crash never leaves a truncated message
+        let tmp_path = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, serde_json::to_vec_pretty(entry)?).await?;
+        tokio::fs::rename(&tmp_path, &path)
+            .await
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    // Writes the entry to `queue` and drops it from the others
+    async fn move_entry(&self, entry: &OutboxEntry, queue: OutboxQueue) -> AnyhowResult<()> {
+        self.write(queue, entry).await?;
+        for other in [OutboxQueue::Pending, OutboxQueue::Sent, OutboxQueue::Dead] {
+            if other != queue {
+                self.remove(other, entry.id()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn remove(&self, queue: OutboxQueue, id: &str) -> AnyhowResult<()> {
+        match tokio::fs::remove_file(self.entry_path(queue, id)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::Action;
+    use std::collections::HashMap;
+
+    fn config(outbox: &std::path::Path, action: &str) -> Config {
+        serde_yaml::from_str(&format!(
+            r#"
+actions:
+  default:
+    {}
+  outbox:
+    path: "{}"
+    max_attempts: 2
+    initial_backoff_secs: 0
+"#,
+            action,
+            outbox.display()
+        ))
+        .unwrap()
+    }
+
+    fn payload() -> ActionPayload {
+        let action = Action {
+            endpoint_id: "send_email".to_string(),
+            parameters: HashMap::new(),
+            similarity: 0.9,
+            negated: false,
+            query: None,
+            language: None,
+            session_id: None,
+            request_id: None,
+        };
+        ActionPayload::new(&action, None)
+    }
+
+    #[tokio::test]
+    async fn test_failures_are_retried_then_dead_lettered() {
+        let directory =
+            std::env::temp_dir().join(format!("matcher-outbox-{}", uuid::Uuid::new_v4()));
+        let failing = config(&directory, r#"{ type: "command", program: "false" }"#);
+        let outbox = Outbox::new(&failing.actions.outbox);
+
+        let payload = payload();
+        let id = outbox.enqueue(payload.clone()).await.unwrap().unwrap();
+        assert!(outbox.enqueue(payload).await.unwrap().is_none());
+
+        assert!(matches!(
+            outbox.deliver(&id, &failing).await.unwrap(),
+            Some(Delivery::Retrying { attempts: 1, .. })
+        ));
+        assert_eq!(outbox.process_due(&failing).await.unwrap(), 0);
+        assert!(outbox.list(OutboxQueue::Pending).await.unwrap().is_empty());
+        assert_eq!(outbox.list(OutboxQueue::Dead).await.unwrap().len(), 1);
+
+        let working = config(&directory, r#"{ type: "noop" }"#);
+        let replayed = outbox.replay(Some(&id), &working).await.unwrap();
+        assert_eq!(replayed, vec![(id, Delivery::Sent)]);
+        assert!(outbox.list(OutboxQueue::Dead).await.unwrap().is_empty());
+        assert_eq!(outbox.list(OutboxQueue::Sent).await.unwrap().len(), 1);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_enqueues_record_once() {
+        let directory =
+            std::env::temp_dir().join(format!("matcher-outbox-{}", uuid::Uuid::new_v4()));
+        let config = config(&directory, r#"{ type: "noop" }"#);
+        let outbox = Outbox::new(&config.actions.outbox);
+
+        let payload = payload();
+        let (first, second) = tokio::join!(
+            outbox.enqueue(payload.clone()),
+            outbox.enqueue(payload.clone())
+        );
+        let recorded = [first.unwrap(), second.unwrap()];
+        assert_eq!(recorded.iter().flatten().count(), 1);
+
+        assert_eq!(outbox.process_due(&config).await.unwrap(), 1);
+        assert!(outbox.enqueue(payload).await.unwrap().is_none());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sent_messages_expire_after_retention() {
+        let directory =
+            std::env::temp_dir().join(format!("matcher-outbox-{}", uuid::Uuid::new_v4()));
+        let config = config(&directory, r#"{ type: "noop" }"#);
+        let outbox = Outbox::new(&config.actions.outbox);
+
+        let payload = payload();
+        let id = outbox.enqueue(payload.clone()).await.unwrap().unwrap();
+        assert_eq!(
+            outbox.deliver(&id, &config).await.unwrap(),
+            Some(Delivery::Sent)
+        );
+        assert_eq!(outbox.purge(None).await.unwrap(), 0);
+        assert!(outbox.enqueue(payload.clone()).await.unwrap().is_none());
+
+        // Backdate the delivery past the retention window
+        let mut sent = outbox.read(OutboxQueue::Sent, &id).await.unwrap().unwrap();
+        sent.sent_at = Some(now() - config.actions.outbox.sent_retention_secs);
+        outbox.write(OutboxQueue::Sent, &sent).await.unwrap();
+        assert_eq!(outbox.enqueue(payload).await.unwrap(), Some(id.clone()));
+
+        assert_eq!(
+            outbox.deliver(&id, &config).await.unwrap(),
+            Some(Delivery::Sent)
+        );
+        assert_eq!(outbox.purge(Some(0)).await.unwrap(), 0);
+        sent = outbox.read(OutboxQueue::Sent, &id).await.unwrap().unwrap();
+        sent.sent_at = Some(now() - 60);
+        outbox.write(OutboxQueue::Sent, &sent).await.unwrap();
+        assert_eq!(outbox.purge(Some(30)).await.unwrap(), 1);
+        assert!(outbox.list(OutboxQueue::Sent).await.unwrap().is_empty());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
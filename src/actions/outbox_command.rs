@@ -0,0 +1,45 @@
+use anyhow::Result as AnyhowResult;
+
+use super::outbox::{Delivery, OutboxQueue};
+use crate::cli::OutboxCommand;
+use crate::config::Config;
+
+pub async fn run_outbox_command(command: OutboxCommand, config: &Config) -> AnyhowResult<()> {
+    let outbox = config.outbox();
+    match command {
+        OutboxCommand::List { dead } => {
+            let queue = if dead {
+                OutboxQueue::Dead
+            } else {
+                OutboxQueue::Pending
+            };
+            let entries = outbox.list(queue).await?;
+            println!("{} {:?} message(s)", entries.len(), queue);
+            for entry in entries {
+                println!(
+                    "{}  {:<30} attempts: {}  last error: {}",
+                    entry.id(),
+                    entry.payload.endpoint_id,
+                    entry.attempts,
+                    entry.last_error.as_deref().unwrap_or("-")
+                );
+            }
+        }
+        OutboxCommand::Replay { id, .. } => {
+            for (id, delivery) in outbox.replay(id.as_deref(), config).await? {
+                match delivery {
+                    Delivery::Sent => println!("{}  sent", id),
+                    Delivery::Retrying { error, .. } => {
+                        println!("{}  failed, will be retried: {}", id, error)
+                    }
+                    Delivery::DeadLettered { error } => println!("{}  failed: {}", id, error),
+                }
+            }
+        }
+        OutboxCommand::Purge { older_than_secs } => {
+            let purged = outbox.purge(older_than_secs).await?;
+            println!("Deleted {} sent message(s)", purged);
+        }
+    }
+    Ok(())
+}
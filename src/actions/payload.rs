@@ -0,0 +1,136 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::Action;
+use crate::config::{Endpoint, ParameterKind};
+
+/// Bumped on every incompatible change of `ActionPayload`, see
+/// `schema/action-payload.v2.json`. Version 1 was
+/// `{timestamp, action, parameters: [values]}`.
+pub const PAYLOAD_VERSION: u32 = 2;
+
+/// The message every dispatcher sends for a matched endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ActionPayload {
+    pub version: u32,
+    // Unique per dispatch, consumers can use it to drop duplicates
+    pub request_id: String,
+    pub session_id: Option<String>,
+    pub timestamp: String,
+    pub endpoint_id: String,
+    pub parameters: BTreeMap<String, TypedValue>,
+    pub similarity: f32,
+    pub negated: bool,
+    pub query: Option<String>,
+    pub language: Option<String>,
+}
+
+/// A parameter value typed after the `kind` declared on the endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum TypedValue {
+    Boolean(bool),
+    Integer(i64),
+    Number(f64),
+    Text(String),
+}
+
+impl TypedValue {
+    // Values that don't parse as their kind are kept as text
+    pub fn parse(value: &str, kind: ParameterKind) -> Self {
+        match kind {
+            ParameterKind::Boolean => value.parse().map(Self::Boolean).ok(),
+            ParameterKind::Integer => value.parse().map(Self::Integer).ok(),
+            ParameterKind::Number => value.parse().map(Self::Number).ok(),
+            ParameterKind::Text | ParameterKind::Email => None,
+        }
+        .unwrap_or_else(|| Self::Text(value.to_string()))
+    }
+}
+
+impl ActionPayload {
+    pub fn new(action: &Action, endpoint: Option<&Endpoint>) -> Self {
+        let kind_of = |name: &str| {
+            endpoint
+                .and_then(|e| e.parameters.iter().find(|p| p.name == name))
+                .map(|p| p.kind)
+                .unwrap_or_default()
+        };
+
+        Self {
+            version: PAYLOAD_VERSION,
+            request_id: action
+                .request_id
+                .clone()
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            session_id: action.session_id.clone(),
+            timestamp: Utc::now().to_rfc3339(),
+            endpoint_id: action.endpoint_id.clone(),
+            parameters: action
+                .parameters
+                .iter()
+                .map(|(name, value)| (name.clone(), TypedValue::parse(value, kind_of(name))))
+                .collect(),
+            similarity: action.similarity,
+            negated: action.negated,
+            query: action.query.clone(),
+            language: action.language.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Parameter;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_payload_matches_published_schema() {
+        let schema: serde_json::Value =
+            serde_json::from_str(include_str!("../../schema/action-payload.v2.json")).unwrap();
+        let endpoint: Endpoint = serde_yaml::from_str(
+            r#"
+id: "perform_calc"
+text: "effectuer un calcul"
+description: "Effectuer un calcul"
+"#,
+        )
+        .map(|mut endpoint: Endpoint| {
+            endpoint.parameters = vec![Parameter {
+                name: "count".to_string(),
+                description: String::new(),
+                required: true,
+                kind: ParameterKind::Integer,
+            }];
+            endpoint
+        })
+        .unwrap();
+        let action = Action {
+            endpoint_id: "perform_calc".to_string(),
+            parameters: HashMap::from([
+                ("count".to_string(), "3".to_string()),
+                ("note".to_string(), "3".to_string()),
+            ]),
+            similarity: 0.9,
+            negated: false,
+            query: Some("calculer 3 fois".to_string()),
+            language: Some("fr".to_string()),
+            session_id: None,
+            request_id: None,
+        };
+
+        let payload = serde_json::to_value(ActionPayload::new(&action, Some(&endpoint))).unwrap();
+        assert_eq!(payload["parameters"]["count"], 3);
+        assert_eq!(payload["parameters"]["note"], "3");
+        assert_eq!(payload["version"], schema["properties"]["version"]["const"]);
+
+        let properties = schema["properties"].as_object().unwrap();
+        let fields = payload.as_object().unwrap();
+        assert!(fields.keys().all(|field| properties.contains_key(field)));
+        for required in schema["required"].as_array().unwrap() {
+            assert!(fields.contains_key(required.as_str().unwrap()));
+        }
+    }
+}
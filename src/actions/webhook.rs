@@ -0,0 +1,63 @@
+use anyhow::{anyhow, Context, Result as AnyhowResult};
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use super::payload::ActionPayload;
+use super::ActionDispatcher;
+
+/// POSTs the action message as JSON to a URL.
+pub struct WebhookDispatcher {
+    client: reqwest::Client,
+    url: String,
+    headers: BTreeMap<String, String>,
+    timeout: Duration,
+}
+
+impl WebhookDispatcher {
+    pub fn new(
+        client: &reqwest::Client,
+        url: &str,
+        headers: &BTreeMap<String, String>,
+        timeout_secs: u64,
+    ) -> Self {
+        Self {
+            client: client.clone(),
+            url: url.to_string(),
+            headers: headers.clone(),
+            timeout: Duration::from_secs(timeout_secs),
+        }
+    }
+}
+
+#[async_trait]
+impl ActionDispatcher for WebhookDispatcher {
+    async fn dispatch(&self, payload: &ActionPayload) -> AnyhowResult<()> {
+        let mut request = self
+            .client
+            .post(&self.url)
+            .timeout(self.timeout)
+            .json(payload);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Webhook {} unreachable", self.url))?;
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "Webhook {} answered {}",
+                self.url,
+                response.status()
+            ));
+        }
+
+        println!(
+            "Sent webhook for endpoint: {} to {}",
+            payload.endpoint_id, self.url
+        );
+        Ok(())
+    }
+}
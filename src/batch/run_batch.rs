@@ -0,0 +1,163 @@
+use anyhow::{Context, Result as AnyhowResult};
+use futures::StreamExt;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tonic::Request;
+
+use crate::config::{Config, LabelledQuery};
+use crate::constants::DB_PATH;
+use crate::database::vector_db::VectorDB;
+use crate::grpc::matcher_service::matcher::matcher_server::Matcher;
+use crate::grpc::matcher_service::matcher::{MatchRequest, MatchResponse};
+use crate::grpc::matcher_service::MatcherService;
+
+/// One line of the output file, in the order of the input.
+#[derive(Debug, Serialize)]
+pub struct BatchResult {
+    // 1-based line of the input file
+    pub line: usize,
+    pub query: String,
+    pub language: String,
+    pub expected: Option<String>,
+    // Whether the best match is the expected endpoint (or no match when the
+    // expected endpoint is null); None for input lines without `endpoint`
+    pub correct: Option<bool>,
+    // Top candidates with parameters and negation, and one intent per clause
+    pub response: Option<MatchResponse>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    pub processed: usize,
+    pub failed: usize,
+    pub labelled: usize,
+    pub correct: usize,
+}
+
+/// Matches every query of a labelled query file (JSONL), at most
+/// `concurrency` at a time, and writes one `BatchResult` per line to
+/// `output`. Actions are not dispatched.
+pub async fn run_batch(
+    config: Arc<Config>,
+    input: &Path,
+    output: &Path,
+    concurrency: usize,
+    default_language: &str,
+) -> AnyhowResult<BatchSummary> {
+    let db = Arc::new(VectorDB::new(DB_PATH, None, false).await?);
+    let service = MatcherService::new(config, db);
+
+    let reader = File::open(input)
+        .await
+        .with_context(|| format!("Failed to read {:?}", input))?;
+    let mut writer = BufWriter::new(
+        File::create(output)
+            .await
+            .with_context(|| format!("Failed to create {:?}", output))?,
+    );
+
+    // Lines are read as the workers free up and written back in input order
+    let lines = futures::stream::unfold(BufReader::new(reader).lines(), |mut lines| async {
+        lines
+            .next_line()
+            .await
+            .transpose()
+            .map(|line| (line, lines))
+    });
+    let mut results = lines
+        .enumerate()
+        .filter(|(_, line)| {
+            std::future::ready(line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        })
+        .map(|(index, line)| {
+            let service = &service;
+            async move {
+                let line = line.with_context(|| format!("Failed to read {:?}", input))?;
+                Ok::<_, anyhow::Error>(
+                    match_line(service, index + 1, &line, default_language).await,
+                )
+            }
+        })
+        .buffered(concurrency.max(1))
+        .boxed();
+
+    let mut summary = BatchSummary::default();
+    while let Some(result) = results.next().await {
+        let result = result?;
+        summary.processed += 1;
+        if result.error.is_some() {
+            summary.failed += 1;
+        }
+        if let Some(correct) = result.correct {
+            summary.labelled += 1;
+            summary.correct += correct as usize;
+        }
+        if summary.processed % 100 == 0 {
+            println!("Matched {} queries...", summary.processed);
+        }
+
+        writer.write_all(&serde_json::to_vec(&result)?).await?;
+        writer.write_all(b"\n").await?;
+    }
+    writer.flush().await?;
+    Ok(summary)
+}
+
+async fn match_line(
+    service: &MatcherService,
+    line: usize,
+    content: &str,
+    default_language: &str,
+) -> BatchResult {
+    // A missing `endpoint` means unlabelled, an explicit null "no endpoint"
+    let parsed = serde_json::from_str::<serde_json::Value>(content).and_then(|value| {
+        let labelled_line = value.get("endpoint").is_some();
+        serde_json::from_value::<LabelledQuery>(value).map(|labelled| (labelled, labelled_line))
+    });
+    let (labelled, labelled_line) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            return BatchResult {
+                line,
+                query: String::new(),
+                language: default_language.to_string(),
+                expected: None,
+                correct: None,
+                response: None,
+                error: Some(format!("Invalid query: {}", e)),
+            }
+        }
+    };
+
+    let language = labelled
+        .language
+        .clone()
+        .unwrap_or_else(|| default_language.to_string());
+    let request = MatchRequest {
+        query: labelled.query.clone(),
+        language: language.clone(),
+        show_all_matches: true,
+        ..Default::default()
+    };
+    let (response, error) = match service.match_query(Request::new(request)).await {
+        Ok(response) => (Some(response.into_inner()), None),
+        Err(status) => (None, Some(status.message().to_string())),
+    };
+
+    let correct = response.as_ref().filter(|_| labelled_line).map(|response| {
+        response.matches.first().map(|m| m.endpoint_id.as_str()) == labelled.endpoint.as_deref()
+    });
+    BatchResult {
+        line,
+        query: labelled.query,
+        language,
+        expected: labelled.endpoint,
+        correct,
+        response,
+        error,
+    }
+}
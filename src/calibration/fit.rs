@@ -0,0 +1,163 @@
+use crate::config::CalibrationMethod;
+
+use super::Calibrator;
+
+/// Fits a calibrator on (similarity, is_correct) samples.
+pub fn fit(samples: &[(f32, bool)], method: CalibrationMethod) -> Calibrator {
+    match method {
+        CalibrationMethod::Platt => fit_platt(samples),
+        CalibrationMethod::Isotonic => fit_isotonic(samples),
+    }
+}
+
+// Logistic regression p = sigmoid(a * s + b) fitted with Newton's method,
+// using Platt's smoothed targets so that separable data does not diverge
+fn fit_platt(samples: &[(f32, bool)]) -> Calibrator {
+    let positives = samples.iter().filter(|(_, label)| *label).count() as f64;
+    let negatives = samples.len() as f64 - positives;
+    let high = (positives + 1.0) / (positives + 2.0);
+    let low = 1.0 / (negatives + 2.0);
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .map(|(score, label)| (*score as f64, if *label { high } else { low }))
+        .collect();
+
+    let loss = |a: f64, b: f64| -> f64 {
+        points
+            .iter()
+            .map(|(s, t)| {
+                let p = sigmoid(a * s + b).clamp(1e-12, 1.0 - 1e-12);
+                -(t * p.ln() + (1.0 - t) * (1.0 - p).ln())
+            })
+            .sum()
+    };
+
+    let mut a = 0.0;
+    let mut b = ((positives + 1.0) / (negatives + 1.0)).ln();
+    let mut current = loss(a, b);
+    for _ in 0..100 {
+        let (mut ga, mut gb, mut haa, mut hab, mut hbb) = (0.0, 0.0, 1e-6, 0.0, 1e-6);
+        for (s, t) in &points {
+            let p = sigmoid(a * s + b);
+            let w = p * (1.0 - p);
+            ga += (p - t) * s;
+            gb += p - t;
+            haa += w * s * s;
+            hab += w * s;
+            hbb += w;
+        }
+        let det = haa * hbb - hab * hab;
+        if det.abs() < 1e-12 {
+            break;
+        }
+        let step_a = (hbb * ga - hab * gb) / det;
+        let step_b = (haa * gb - hab * ga) / det;
+
+        // Backtrack until the loss decreases
+        let mut scale = 1.0;
+        let mut improved = false;
+        while scale > 1e-4 {
+            let (na, nb) = (a - scale * step_a, b - scale * step_b);
+            let candidate = loss(na, nb);
+            if candidate < current {
+                a = na;
+                b = nb;
+                current = candidate;
+                improved = true;
+                break;
+            }
+            scale /= 2.0;
+        }
+        if !improved || (step_a.abs() < 1e-9 && step_b.abs() < 1e-9) {
+            break;
+        }
+    }
+
+    Calibrator::Platt {
+        a: a as f32,
+        b: b as f32,
+    }
+}
+
+// Pool adjacent violators: merge neighbouring blocks until the mean label is
+// non-decreasing with the score, then keep one point per block
+fn fit_isotonic(samples: &[(f32, bool)]) -> Calibrator {
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    // (sum of scores, sum of labels, count)
+    let mut blocks: Vec<(f64, f64, f64)> = Vec::new();
+    for (score, label) in sorted {
+        blocks.push((score as f64, if label { 1.0 } else { 0.0 }, 1.0));
+        while blocks.len() >= 2 {
+            let last = blocks[blocks.len() - 1];
+            let previous = blocks[blocks.len() - 2];
+            if previous.1 / previous.2 < last.1 / last.2 {
+                break;
+            }
+            blocks.pop();
+            let merged = blocks.last_mut().unwrap();
+            merged.0 += last.0;
+            merged.1 += last.1;
+            merged.2 += last.2;
+        }
+    }
+
+    Calibrator::Isotonic {
+        scores: blocks.iter().map(|b| (b.0 / b.2) as f32).collect(),
+        probabilities: blocks.iter().map(|b| (b.1 / b.2) as f32).collect(),
+    }
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples() -> Vec<(f32, bool)> {
+        vec![
+            (0.95, true),
+            (0.90, true),
+            (0.85, true),
+            (0.80, false),
+            (0.82, true),
+            (0.70, false),
+            (0.65, true),
+            (0.60, false),
+            (0.50, false),
+            (0.40, false),
+        ]
+    }
+
+    #[test]
+    fn test_platt_is_increasing() {
+        let calibrator = fit(&samples(), CalibrationMethod::Platt);
+        let low = calibrator.apply(0.4);
+        let high = calibrator.apply(0.95);
+        assert!(low < 0.3, "low = {}", low);
+        assert!(high > 0.7, "high = {}", high);
+        assert!(calibrator.apply(0.6) < calibrator.apply(0.8));
+    }
+
+    #[test]
+    fn test_isotonic_is_monotonic() {
+        let calibrator = fit(&samples(), CalibrationMethod::Isotonic);
+        let Calibrator::Isotonic {
+            scores,
+            probabilities,
+        } = &calibrator
+        else {
+            panic!("expected an isotonic calibrator");
+        };
+        assert!(scores.windows(2).all(|w| w[0] < w[1]));
+        assert!(probabilities.windows(2).all(|w| w[0] < w[1]));
+
+        assert_eq!(calibrator.apply(0.1), 0.0);
+        assert_eq!(calibrator.apply(1.0), 1.0);
+        let middle = calibrator.apply(0.75);
+        assert!(middle > 0.0 && middle < 1.0);
+    }
+}
@@ -0,0 +1,149 @@
+pub mod fit;
+pub mod run_calibration;
+
+use anyhow::{Context, Result as AnyhowResult};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::config::{AggregationStrategy, Config, FusionMethod, SearchMode, SearchOptions};
+
+pub const CALIBRATION_FILE: &str = "calibration.json";
+
+/// Maps a raw similarity to the probability that the endpoint is correct.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Calibrator {
+    Platt {
+        a: f32,
+        b: f32,
+    },
+    // Points of a non-decreasing curve, interpolated linearly
+    Isotonic {
+        scores: Vec<f32>,
+        probabilities: Vec<f32>,
+    },
+}
+
+impl Calibrator {
+    pub fn apply(&self, similarity: f32) -> f32 {
+        match self {
+            Calibrator::Platt { a, b } => 1.0 / (1.0 + (-(a * similarity + b)).exp()),
+            Calibrator::Isotonic {
+                scores,
+                probabilities,
+            } => {
+                let (Some(first), Some(last)) = (scores.first(), scores.last()) else {
+                    return similarity.clamp(0.0, 1.0);
+                };
+                if similarity <= *first {
+                    return probabilities[0];
+                }
+                if similarity >= *last {
+                    return probabilities[probabilities.len() - 1];
+                }
+                let idx = scores.partition_point(|s| *s <= similarity);
+                let (x0, x1) = (scores[idx - 1], scores[idx]);
+                let (y0, y1) = (probabilities[idx - 1], probabilities[idx]);
+                y0 + (y1 - y0) * (similarity - x0) / (x1 - x0)
+            }
+        }
+    }
+}
+
+/// Search settings that shape the similarities a calibration is fitted on.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct ScoringSettings {
+    pub mode: SearchMode,
+    pub fusion: FusionMethod,
+    pub rerank: bool,
+    pub aggregation: AggregationStrategy,
+}
+
+impl ScoringSettings {
+    /// Settings used by a search with `options` over `config`.
+    pub fn resolve(config: &Config, options: &SearchOptions) -> Self {
+        Self {
+            mode: options.mode.unwrap_or(config.search.mode),
+            fusion: options.fusion.unwrap_or(config.search.fusion),
+            rerank: options.rerank.unwrap_or(config.rerank.enabled),
+            aggregation: options.aggregation.unwrap_or(config.search.aggregation),
+        }
+    }
+}
+
+/// Calibration fitted for one index, stored next to it as
+/// `<index>.calibration.json`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Calibration {
+    // Embedding model the similarities were computed with
+    pub model: String,
+    pub scoring: ScoringSettings,
+    pub fitted_at: String,
+    pub samples: usize,
+    #[serde(flatten)]
+    pub calibrator: Calibrator,
+}
+
+impl Calibration {
+    // Next to the index directory; --reload deletes it along with the index
+    pub fn path(db_path: &str) -> PathBuf {
+        let db_path = Path::new(db_path);
+        let index_name = db_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        db_path.with_file_name(format!("{}.{}", index_name, CALIBRATION_FILE))
+    }
+
+    /// Loads the calibration of the index at `db_path`, if one was fitted.
+    pub fn load(db_path: &str) -> AnyhowResult<Option<Self>> {
+        let path = Self::path(db_path);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content =
+            std::fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        let calibration = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid calibration file {:?}", path))?;
+        Ok(Some(calibration))
+    }
+
+    pub fn save(&self, db_path: &str) -> AnyhowResult<()> {
+        let path = Self::path(db_path);
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    pub fn apply(&self, similarity: f32) -> f32 {
+        self.calibrator.apply(similarity).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_outside_index() {
+        assert_eq!(
+            Calibration::path("data/mydb"),
+            PathBuf::from("data/mydb.calibration.json")
+        );
+    }
+
+    #[test]
+    fn test_scoring_settings_follow_options() {
+        let config = Config::default();
+        let defaults = ScoringSettings::resolve(&config, &SearchOptions::default());
+        let hybrid = ScoringSettings::resolve(
+            &config,
+            &SearchOptions {
+                mode: Some(SearchMode::Hybrid),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(defaults.mode, SearchMode::Vector);
+        assert_ne!(defaults, hybrid);
+    }
+}
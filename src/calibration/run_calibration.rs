@@ -0,0 +1,82 @@
+use anyhow::Result as AnyhowResult;
+use chrono::Utc;
+
+use super::fit::fit;
+use super::{Calibration, ScoringSettings};
+use crate::candle::MODEL_PATH;
+use crate::config::{CalibrationMethod, Config, LabelledQuery, SearchOptions};
+use crate::constants::DEFAULT_LANGUAGE;
+use crate::database::vector_db::VectorDB;
+
+// Candidates per query: the expected endpoint is a positive sample, every
+// other returned endpoint a negative one
+const CANDIDATES_PER_QUERY: usize = 5;
+
+/// Runs every labelled query through the search pipeline and fits `method`
+/// on the raw endpoint similarities.
+pub async fn run_calibration(
+    db: &VectorDB,
+    config: &Config,
+    queries: &[LabelledQuery],
+    method: CalibrationMethod,
+) -> AnyhowResult<Calibration> {
+    // Thresholds would hide the low-scoring candidates the fit needs
+    let config = &config.without_thresholds();
+    let mut samples = Vec::new();
+    for labelled in queries {
+        let language = labelled.language.as_deref().unwrap_or(DEFAULT_LANGUAGE);
+        let outcome = db
+            .search_similar_with_options(
+                &labelled.query,
+                language,
+                CANDIDATES_PER_QUERY,
+                config,
+                &SearchOptions::default(),
+            )
+            .await?;
+        samples.extend(outcome.results.iter().map(|result| {
+            (
+                result.similarity,
+                labelled.endpoint.as_deref() == Some(result.endpoint_id.as_str()),
+            )
+        }));
+    }
+
+    let positives = samples.iter().filter(|(_, label)| *label).count();
+    if positives == 0 || positives == samples.len() {
+        return Err(anyhow::anyhow!(
+            "Calibration needs both correct and incorrect matches, got {} correct out of {}",
+            positives,
+            samples.len()
+        ));
+    }
+
+    let calibration = Calibration {
+        model: MODEL_PATH.to_string(),
+        scoring: ScoringSettings::resolve(config, &SearchOptions::default()),
+        fitted_at: Utc::now().to_rfc3339(),
+        samples: samples.len(),
+        calibrator: fit(&samples, method),
+    };
+
+    let brier = |predict: &dyn Fn(f32) -> f32| -> f32 {
+        samples
+            .iter()
+            .map(|(score, label)| (predict(*score) - if *label { 1.0 } else { 0.0 }).powi(2))
+            .sum::<f32>()
+            / samples.len() as f32
+    };
+    println!(
+        "Fitted {:?} calibration on {} samples ({} correct)",
+        method,
+        samples.len(),
+        positives
+    );
+    println!(
+        "Brier score: {:.4} raw, {:.4} calibrated",
+        brier(&|s| s.clamp(0.0, 1.0)),
+        brier(&|s| calibration.apply(s))
+    );
+
+    Ok(calibration)
+}
@@ -0,0 +1,137 @@
+use anyhow::{Context, Result as AnyhowResult};
+use candle_core::{Device, IndexOp, Tensor};
+use candle_nn::{linear, Linear, Module, VarBuilder};
+use candle_transformers::models::bert::{BertModel, Config, DTYPE};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+use tokenizers::{PaddingParams, Tokenizer};
+
+// One load per model path; a failed load is remembered instead of being
+// retried on every query
+type LoadedCrossEncoder = Result<Arc<CrossEncoder>, String>;
+static CROSS_ENCODERS: OnceLock<Mutex<HashMap<String, LoadedCrossEncoder>>> = OnceLock::new();
+
+/// BERT sequence classification model scoring (query, pattern) pairs jointly,
+/// as exported by sentence-transformers cross-encoders.
+pub struct CrossEncoder {
+    model: BertModel,
+    pooler: Linear,
+    classifier: Linear,
+    tokenizer: Tokenizer,
+}
+
+/// Loads the cross-encoder at `model_path` on first use; later calls reuse
+/// the model, or the error when it could not be loaded. Blocks while loading.
+pub fn get_cross_encoder(model_path: &str) -> AnyhowResult<Arc<CrossEncoder>> {
+    let mut cross_encoders = CROSS_ENCODERS
+        .get_or_init(Default::default)
+        .lock()
+        .map_err(|_| anyhow::anyhow!("Cross-encoder cache poisoned"))?;
+    cross_encoders
+        .entry(model_path.to_string())
+        .or_insert_with(|| {
+            load_cross_encoder(model_path)
+                .map(Arc::new)
+                .map_err(|e| format!("{:#}", e))
+        })
+        .clone()
+        .map_err(|e| anyhow::anyhow!(e))
+}
+
+pub fn load_cross_encoder(model_path: &str) -> AnyhowResult<CrossEncoder> {
+    let model_path = Path::new(model_path);
+
+    let config_path = model_path.join("config.json");
+    let tokenizer_path = model_path.join("tokenizer.json");
+    let safetensors_path = model_path.join("model.safetensors");
+    let weights_path = model_path.join("model.ot");
+
+    if !config_path.exists() {
+        return Err(anyhow::anyhow!(
+            "Cross-encoder config file not found at {:?}",
+            config_path
+        ));
+    }
+    if !tokenizer_path.exists() {
+        return Err(anyhow::anyhow!(
+            "Cross-encoder tokenizer file not found at {:?}",
+            tokenizer_path
+        ));
+    }
+
+    let config = std::fs::read_to_string(config_path)?;
+    let bert_config: Config = serde_json::from_str(&config)?;
+    let hidden_size = serde_json::from_str::<serde_json::Value>(&config)?["hidden_size"]
+        .as_u64()
+        .context("Cross-encoder config has no hidden_size")? as usize;
+
+    let mut tokenizer = Tokenizer::from_file(&tokenizer_path)
+        .map_err(|e| anyhow::anyhow!("Failed to load cross-encoder tokenizer: {}", e))?;
+    tokenizer.with_padding(Some(PaddingParams {
+        strategy: tokenizers::PaddingStrategy::BatchLongest,
+        ..Default::default()
+    }));
+
+    let vb = if safetensors_path.exists() {
+        // SAFETY: the weights file is not modified while the model is loaded
+        unsafe { VarBuilder::from_mmaped_safetensors(&[safetensors_path], DTYPE, &Device::Cpu)? }
+    } else if weights_path.exists() {
+        VarBuilder::from_pth(&weights_path, DTYPE, &Device::Cpu)?
+    } else {
+        return Err(anyhow::anyhow!(
+            "Cross-encoder weights not found at {:?} or {:?}",
+            safetensors_path,
+            weights_path
+        ));
+    };
+
+    let model = BertModel::load(vb.clone(), &bert_config)?;
+    // Depending on the export, the pooler lives under "bert." or at the root
+    let pooler = linear(hidden_size, hidden_size, vb.pp("bert.pooler.dense"))
+        .or_else(|_| linear(hidden_size, hidden_size, vb.pp("pooler.dense")))?;
+    let classifier = linear(hidden_size, 1, vb.pp("classifier"))?;
+
+    Ok(CrossEncoder {
+        model,
+        pooler,
+        classifier,
+        tokenizer,
+    })
+}
+
+impl CrossEncoder {
+    /// Relevance of each pattern for the query, in [0, 1].
+    pub fn score(&self, query: &str, patterns: &[&str]) -> AnyhowResult<Vec<f32>> {
+        if patterns.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let pairs: Vec<(&str, &str)> = patterns.iter().map(|p| (query, *p)).collect();
+        let encodings = self
+            .tokenizer
+            .encode_batch(pairs, true)
+            .map_err(|e| anyhow::anyhow!("Failed to encode pairs: {}", e))?;
+
+        let stack = |rows: Vec<&[u32]>| -> AnyhowResult<Tensor> {
+            let rows = rows
+                .into_iter()
+                .map(|row| Ok(Tensor::new(row, &Device::Cpu)?))
+                .collect::<AnyhowResult<Vec<_>>>()?;
+            Ok(Tensor::stack(&rows, 0)?)
+        };
+        let token_ids = stack(encodings.iter().map(|e| e.get_ids()).collect())?;
+        let token_type_ids = stack(encodings.iter().map(|e| e.get_type_ids()).collect())?;
+        let attention_mask = stack(encodings.iter().map(|e| e.get_attention_mask()).collect())?;
+
+        let hidden = self
+            .model
+            .forward(&token_ids, &token_type_ids, Some(&attention_mask))?;
+        let cls = hidden.i((.., 0))?;
+        let pooled = self.pooler.forward(&cls)?.tanh()?;
+        let logits = self.classifier.forward(&pooled)?.squeeze(1)?;
+        let scores = candle_nn::ops::sigmoid(&logits)?;
+
+        Ok(scores.to_vec1::<f32>()?)
+    }
+}
@@ -1,3 +1,4 @@
+pub mod cross_encoder;
 pub mod get_embeddings;
 pub mod load_model;
 
@@ -1,5 +1,8 @@
 // src/cli.rs
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::config::CalibrationMethod;
 
 #[derive(Parser)]
 #[command(
@@ -8,9 +11,11 @@ use clap::Parser;
     long_about = "A tool for semantically matching natural language queries to API endpoints using embeddings",
     version,
     author = "Mohamed Bennekrouf <mb@mb.ch>",
-    help_template = "{about}\n\nUSAGE:\n    {usage}\n\n{options}"
+    help_template = "{about}\n\nUSAGE:\n    {usage}\n\n{subcommands}\n\n{options}"
 )]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
     #[arg(long, default_value = "false")]
     pub reload: bool,
     #[arg(short, long)]
@@ -23,6 +28,88 @@ pub struct Args {
     pub language: String,
     #[arg(long)]
     pub server: bool,
+    /// Match requests read from the Iggy request topic (worker mode)
+    #[arg(long, conflicts_with = "server")]
+    pub consume: bool,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Fit confidence calibration on a labelled query file (JSONL)
+    Calibrate {
+        #[arg(long)]
+        dataset: PathBuf,
+        #[arg(long, value_enum, default_value_t = CalibrationMethod::Platt)]
+        method: CalibrationMethod,
+    },
+    /// Report accuracy, per-endpoint metrics and a confusion matrix on a labelled query file (JSONL)
+    Eval {
+        #[arg(long)]
+        dataset: PathBuf,
+        /// Write the report as JSON
+        #[arg(long)]
+        json: Option<PathBuf>,
+        /// Write the report as Markdown instead of printing it
+        #[arg(long)]
+        markdown: Option<PathBuf>,
+    },
+    /// Sweep the thresholds and ambiguity margin on a labelled query file (JSONL) and recommend values
+    Tune {
+        #[arg(long)]
+        dataset: PathBuf,
+        /// Precision to reach while answering as many queries as possible
+        #[arg(long, default_value_t = 0.95)]
+        target_precision: f64,
+        /// Share of in-scope queries to answer correctly, as precisely as possible
+        #[arg(long, conflicts_with = "target_precision")]
+        target_coverage: Option<f64>,
+        /// Step of the sweep
+        #[arg(long, default_value_t = 0.01)]
+        step: f64,
+        /// Save the recommended values next to the endpoints file
+        #[arg(long)]
+        write: bool,
+        /// Write without asking for confirmation
+        #[arg(long, requires = "write")]
+        yes: bool,
+    },
+    /// Match every query of a JSONL file and write the results as JSONL
+    Batch {
+        #[arg(long)]
+        input: PathBuf,
+        #[arg(long)]
+        output: PathBuf,
+        /// Queries matched at the same time
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+    },
+    /// Inspect and replay the action outbox
+    Outbox {
+        #[command(subcommand)]
+        command: OutboxCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum OutboxCommand {
+    /// List pending messages, or dead letters with --dead
+    List {
+        #[arg(long)]
+        dead: bool,
+    },
+    /// Send dead letters again, one by id or all of them
+    Replay {
+        #[arg(required_unless_present = "all")]
+        id: Option<String>,
+        #[arg(long, conflicts_with = "id")]
+        all: bool,
+    },
+    /// Delete sent messages older than actions.outbox.sent_retention_secs
+    Purge {
+        /// Retention to apply instead of the configured one, in seconds
+        #[arg(long)]
+        older_than_secs: Option<u64>,
+    },
 }
 
 pub fn parse_args() -> Args {
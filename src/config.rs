@@ -1,27 +1,67 @@
-use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::Path};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use crate::actions::outbox::Outbox;
+use crate::constants::DEFAULT_LANGUAGE;
+use crate::evaluation::tune_thresholds::ThresholdOverrides;
+use crate::messaging::iggy_pool::IggyPool;
+use crate::preprocessing::spell_correction::SpellCorrector;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Parameter {
     pub name: String,
     pub description: String,
     pub required: bool,
+    // Values given interactively are validated and normalised by kind
+    #[serde(default)]
+    pub kind: ParameterKind,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ParameterKind {
+    #[default]
+    Text,
+    Email,
+    Integer,
+    Number,
+    Boolean,
 }
 
 #[derive(Debug, Clone)]
 pub struct SearchResult {
     pub endpoint_id: String,
     pub pattern: String,
+    pub language: String,
     pub similarity: f32,
+    // Calibrated probability that the endpoint is the right one; equals the
+    // clamped similarity until a calibration has been fitted
+    pub confidence: f32,
+    // Row is one of the endpoint's negative examples
+    pub is_negative: bool,
     pub parameters: HashMap<String, String>,
     pub parameter_analysis: ParameterAnalysis,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 pub struct ProcessedQuery {
     pub cleaned_text: String,
     pub parameters: HashMap<String, String>,
     pub is_negated: bool,
+    // Query after spell correction, when any correction was applied
+    pub corrected_text: Option<String>,
+    pub corrections: Vec<SpellCorrection>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpellCorrection {
+    pub original: String,
+    pub corrected: String,
+    pub distance: usize,
 }
 
 //pub struct SearchAttempt {
@@ -34,11 +74,54 @@ pub struct ProcessedQuery {
 pub struct Endpoint {
     pub id: String,
     pub text: String,
-    #[serde(default)]
-    pub patterns: Vec<String>,
+    // Patterns keyed by language code. A plain list is accepted for
+    // backward compatibility and assigned to DEFAULT_LANGUAGE.
+    #[serde(default, deserialize_with = "deserialize_patterns")]
+    pub patterns: BTreeMap<String, Vec<String>>,
+    // Queries closest to one of these are penalised or excluded for this endpoint
+    #[serde(default, deserialize_with = "deserialize_patterns")]
+    pub negative_patterns: BTreeMap<String, Vec<String>>,
     pub description: String,
     #[serde(default)]
     pub parameters: Vec<Parameter>,
+    // Overrides search.threshold for this endpoint
+    #[serde(default)]
+    pub threshold: Option<f32>,
+    // Whether interactive sessions prompt for missing optional parameters
+    #[serde(default)]
+    pub optional_parameters: OptionalParameterPolicy,
+    // Where the action is sent once matched, `actions.default` when unset
+    #[serde(default)]
+    pub action: Option<ActionConfig>,
+    // What to do with the action when the query is negated
+    #[serde(default)]
+    pub negation: NegationPolicy,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum NegationPolicy {
+    // Nothing is dispatched
+    #[default]
+    Suppress,
+    // The declared opposite endpoint is dispatched instead
+    Inverse {
+        endpoint: String,
+    },
+    // The action is dispatched with `negated: true` for the backend to handle
+    Dispatch,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OptionalParameterPolicy {
+    // Only required parameters are prompted
+    #[default]
+    Never,
+    // Every missing optional parameter is prompted, each can be skipped
+    Ask,
+    // Optional parameters are prompted until the user skips one
+    AskOnce,
 }
 
 #[derive(Debug, Clone)]
@@ -58,48 +141,970 @@ pub struct LanguagePatterns {
     pub negations: Vec<NegationPattern>,
     pub articles: Vec<&'static str>,
     pub polite_phrases: Vec<&'static str>,
+    // Words separating two intents in a compound query
+    pub conjunctions: Vec<&'static str>,
+    // Words marking a query as a follow-up of the previous one ("aussi", "instead")
+    pub follow_up_markers: Vec<&'static str>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LanguageMode {
+    // Only search patterns written in the request language
+    #[default]
+    Filter,
+    // Search all patterns, adding `language_boost` to same-language matches
+    Boost,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchMode {
+    // Cosine similarity on the pattern embeddings only
+    #[default]
+    Vector,
+    // Vector similarity fused with full-text (BM25) scores on the pattern column
+    Hybrid,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FusionMethod {
+    // vector_weight * similarity + (1 - vector_weight) * normalised BM25 score
+    WeightedSum,
+    // Sum of 1 / (rrf_k + rank) over both result lists
+    #[default]
+    ReciprocalRank,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregationStrategy {
+    // Similarity of the best matching pattern
+    #[default]
+    Max,
+    // Mean similarity of the `aggregation_top_k` best patterns
+    MeanTopK,
+    // Mean of all matching patterns weighted by softmax(similarity / softmax_temperature)
+    Softmax,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum CalibrationMethod {
+    // Logistic regression on the similarity
+    #[default]
+    Platt,
+    // Monotonic step function, needs more labelled queries
+    Isotonic,
+}
+
+/// One line of a labelled query file (JSONL). `endpoint` is null for
+/// queries that should not match any endpoint.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LabelledQuery {
+    pub query: String,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    // Expected extraction and negation, only evaluated when present
+    #[serde(default)]
+    pub parameters: Option<BTreeMap<String, String>>,
+    #[serde(default)]
+    pub negated: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchConfig {
+    #[serde(default)]
+    pub language_mode: LanguageMode,
+    #[serde(default = "default_language_boost")]
+    pub language_boost: f32,
+    // In filter mode, search every language when the request language has no match
+    #[serde(default = "default_true")]
+    pub cross_lingual_fallback: bool,
+    #[serde(default)]
+    pub mode: SearchMode,
+    #[serde(default)]
+    pub fusion: FusionMethod,
+    #[serde(default = "default_vector_weight")]
+    pub vector_weight: f32,
+    #[serde(default = "default_rrf_k")]
+    pub rrf_k: f32,
+    // Matches below this similarity are discarded, unless the endpoint overrides it
+    #[serde(default)]
+    pub threshold: f32,
+    // A best match closer than this to the runner-up is ambiguous and
+    // discarded; 0 disables the check
+    #[serde(default)]
+    pub ambiguity_margin: f32,
+    #[serde(default)]
+    pub aggregation: AggregationStrategy,
+    #[serde(default = "default_aggregation_top_k")]
+    pub aggregation_top_k: usize,
+    #[serde(default = "default_softmax_temperature")]
+    pub softmax_temperature: f32,
+    // Pattern rows fetched per requested endpoint before aggregation
+    #[serde(default = "default_overfetch_factor")]
+    pub overfetch_factor: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            language_mode: LanguageMode::default(),
+            language_boost: default_language_boost(),
+            cross_lingual_fallback: true,
+            mode: SearchMode::default(),
+            fusion: FusionMethod::default(),
+            vector_weight: default_vector_weight(),
+            rrf_k: default_rrf_k(),
+            threshold: 0.0,
+            ambiguity_margin: 0.0,
+            aggregation: AggregationStrategy::default(),
+            aggregation_top_k: default_aggregation_top_k(),
+            softmax_temperature: default_softmax_temperature(),
+            overfetch_factor: default_overfetch_factor(),
+        }
+    }
+}
+
+// Per-request overrides of the search configuration
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchOptions {
+    pub mode: Option<SearchMode>,
+    pub fusion: Option<FusionMethod>,
+    pub rerank: Option<bool>,
+    pub aggregation: Option<AggregationStrategy>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RerankConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_cross_encoder_path")]
+    pub model_path: String,
+    // Number of bi-encoder candidates scored by the cross-encoder
+    #[serde(default = "default_rerank_top_k")]
+    pub top_k: usize,
+    // 1.0 replaces the similarity with the cross-encoder score, lower values blend both
+    #[serde(default = "default_rerank_weight")]
+    pub weight: f32,
+}
+
+impl Default for RerankConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            model_path: default_cross_encoder_path(),
+            top_k: default_rerank_top_k(),
+            weight: default_rerank_weight(),
+        }
+    }
+}
+
+fn default_cross_encoder_path() -> String {
+    "models/cross-encoder".to_string()
+}
+
+fn default_rerank_top_k() -> usize {
+    10
+}
+
+fn default_rerank_weight() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NegativeMode {
+    // Subtract `penalty` from the endpoint's similarity
+    #[default]
+    Penalize,
+    // Remove the endpoint from the results
+    Exclude,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NegativesConfig {
+    #[serde(default)]
+    pub mode: NegativeMode,
+    #[serde(default = "default_negative_penalty")]
+    pub penalty: f32,
+}
+
+impl Default for NegativesConfig {
+    fn default() -> Self {
+        Self {
+            mode: NegativeMode::default(),
+            penalty: default_negative_penalty(),
+        }
+    }
+}
+
+fn default_negative_penalty() -> f32 {
+    0.2
+}
+
+// An endpoint penalised or excluded because the query was closer to one of
+// its negative examples than to any of its patterns
+#[derive(Debug, Clone)]
+pub struct Suppression {
+    pub endpoint_id: String,
+    pub negative_pattern: String,
+    pub similarity: f32,
+    pub excluded: bool,
+    pub reason: String,
+}
+
+// Everything search_similar found, including what was filtered out
+#[derive(Debug, Clone, Default)]
+pub struct SearchOutcome {
+    pub results: Vec<SearchResult>,
+    pub best_similarity: f32,
+    pub suppressed: Vec<Suppression>,
+    // The query as it was searched, preprocessed and spell-corrected once
+    pub processed: ProcessedQuery,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PreprocessingConfig {
+    #[serde(default)]
+    pub spell_correction: bool,
+    #[serde(default = "default_max_edit_distance")]
+    pub max_edit_distance: usize,
+    // Split compound queries on conjunctions and punctuation, one intent per clause
+    #[serde(default)]
+    pub split_intents: bool,
+}
+
+impl Default for PreprocessingConfig {
+    fn default() -> Self {
+        Self {
+            spell_correction: false,
+            max_edit_distance: default_max_edit_distance(),
+            split_intents: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ActionConfig {
+    // Publish the action message to an Iggy topic, `iggy.stream` and
+    // `iggy.topic` when not overridden
+    Iggy {
+        #[serde(default)]
+        stream: Option<String>,
+        #[serde(default)]
+        topic: Option<String>,
+    },
+    // POST the action message as JSON
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+        #[serde(default = "default_webhook_timeout_secs")]
+        timeout_secs: u64,
+    },
+    // Run a program with the action message on its standard input
+    Command {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    // Append the action message to a JSON lines file
+    File {
+        path: String,
+    },
+    Stdout,
+    Noop,
+}
+
+impl Default for ActionConfig {
+    fn default() -> Self {
+        Self::Iggy {
+            stream: None,
+            topic: None,
+        }
+    }
+}
+
+fn default_webhook_timeout_secs() -> u64 {
+    10
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ActionsConfig {
+    // Used by endpoints without an `action` of their own
+    #[serde(default)]
+    pub default: ActionConfig,
+    #[serde(default)]
+    pub outbox: OutboxConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OutboxConfig {
+    // Record every dispatch on disk before sending it, and retry failures
+    #[serde(default = "default_outbox_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_outbox_path")]
+    pub path: String,
+    // Attempts before a message is moved to the dead-letter store
+    #[serde(default = "default_outbox_max_attempts")]
+    pub max_attempts: u32,
+    // Delay before the first retry, doubled after every failure
+    #[serde(default = "default_outbox_initial_backoff_secs")]
+    pub initial_backoff_secs: u64,
+    #[serde(default = "default_outbox_max_backoff_secs")]
+    pub max_backoff_secs: u64,
+    // How long delivered messages, and so their idempotency keys, are kept;
+    // 0 keeps them forever
+    #[serde(default = "default_outbox_sent_retention_secs")]
+    pub sent_retention_secs: u64,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_outbox_enabled(),
+            path: default_outbox_path(),
+            max_attempts: default_outbox_max_attempts(),
+            initial_backoff_secs: default_outbox_initial_backoff_secs(),
+            max_backoff_secs: default_outbox_max_backoff_secs(),
+            sent_retention_secs: default_outbox_sent_retention_secs(),
+        }
+    }
+}
+
+fn default_outbox_enabled() -> bool {
+    true
+}
+
+fn default_outbox_path() -> String {
+    "data/outbox".to_string()
+}
+
+fn default_outbox_max_attempts() -> u32 {
+    5
+}
+
+fn default_outbox_initial_backoff_secs() -> u64 {
+    2
+}
+
+fn default_outbox_max_backoff_secs() -> u64 {
+    300
+}
+
+fn default_outbox_sent_retention_secs() -> u64 {
+    7 * 24 * 3600
+}
+
+impl ActionsConfig {
+    pub fn for_endpoint<'a>(&'a self, endpoint: Option<&'a Endpoint>) -> &'a ActionConfig {
+        endpoint
+            .and_then(|e| e.action.as_ref())
+            .unwrap_or(&self.default)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IggyTransport {
+    #[default]
+    Tcp,
+    Quic,
+    Http,
+}
+
+impl std::str::FromStr for IggyTransport {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "tcp" => Ok(Self::Tcp),
+            "quic" => Ok(Self::Quic),
+            "http" => Ok(Self::Http),
+            _ => Err(anyhow::anyhow!("Unknown Iggy transport '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IggyConfig {
+    #[serde(default)]
+    pub transport: IggyTransport,
+    // host:port for tcp and quic, the API URL for http
+    #[serde(default = "default_iggy_address")]
+    pub address: String,
+    #[serde(default = "default_iggy_credential")]
+    pub username: String,
+    #[serde(default = "default_iggy_credential")]
+    pub password: String,
+    // Files holding the credentials (e.g. Docker secrets), read at connection
+    // time and preferred over the inline values
+    #[serde(default)]
+    pub username_file: Option<String>,
+    #[serde(default)]
+    pub password_file: Option<String>,
+    // TLS for the tcp transport
+    #[serde(default)]
+    pub tls: bool,
+    // TLS domain for tcp, server name for quic
+    #[serde(default)]
+    pub tls_domain: Option<String>,
+    #[serde(default = "default_iggy_stream")]
+    pub stream: String,
+    #[serde(default = "default_iggy_topic")]
+    pub topic: String,
+}
+
+impl Default for IggyConfig {
+    fn default() -> Self {
+        Self {
+            transport: IggyTransport::default(),
+            address: default_iggy_address(),
+            username: default_iggy_credential(),
+            password: default_iggy_credential(),
+            username_file: None,
+            password_file: None,
+            tls: false,
+            tls_domain: None,
+            stream: default_iggy_stream(),
+            topic: default_iggy_topic(),
+        }
+    }
+}
+
+impl IggyConfig {
+    /// Overrides settings from `MATCHER_IGGY_*` variables, e.g.
+    /// `MATCHER_IGGY_ADDRESS` or `MATCHER_IGGY_PASSWORD_FILE`.
+    pub fn apply_env_overrides(&mut self) -> anyhow::Result<()> {
+        self.apply_overrides(|name| std::env::var(format!("MATCHER_IGGY_{}", name)).ok())
+    }
+
+    fn apply_overrides(&mut self, lookup: impl Fn(&str) -> Option<String>) -> anyhow::Result<()> {
+        if let Some(transport) = lookup("TRANSPORT") {
+            self.transport = transport.parse()?;
+        }
+        if let Some(tls) = lookup("TLS") {
+            self.tls = matches!(tls.to_lowercase().as_str(), "1" | "true" | "yes");
+        }
+        for (name, field) in [
+            ("ADDRESS", &mut self.address),
+            ("USERNAME", &mut self.username),
+            ("PASSWORD", &mut self.password),
+            ("STREAM", &mut self.stream),
+            ("TOPIC", &mut self.topic),
+        ] {
+            if let Some(value) = lookup(name) {
+                *field = value;
+            }
+        }
+        for (name, field) in [
+            ("USERNAME_FILE", &mut self.username_file),
+            ("PASSWORD_FILE", &mut self.password_file),
+            ("TLS_DOMAIN", &mut self.tls_domain),
+        ] {
+            if let Some(value) = lookup(name) {
+                *field = Some(value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Username and password, read from their files when configured.
+    pub fn credentials(&self) -> anyhow::Result<(String, String)> {
+        let read = |inline: &str, file: &Option<String>| match file {
+            Some(path) => std::fs::read_to_string(path)
+                .map(|secret| secret.trim().to_string())
+                .map_err(|e| anyhow::anyhow!("Failed to read Iggy secret {}: {}", path, e)),
+            None => Ok(inline.to_string()),
+        };
+        Ok((
+            read(&self.username, &self.username_file)?,
+            read(&self.password, &self.password_file)?,
+        ))
+    }
+}
+
+fn default_iggy_address() -> String {
+    "127.0.0.1:8090".to_string()
+}
+
+// The root user of a fresh Iggy server, only meant for local development
+pub(crate) fn default_iggy_credential() -> String {
+    "iggy".to_string()
+}
+
+fn default_iggy_stream() -> String {
+    "gibro".to_string()
+}
+
+fn default_iggy_topic() -> String {
+    "notification".to_string()
+}
+
+/// Worker mode (`matcher --consume`): match requests read from an Iggy topic,
+/// results published to another one.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConsumerConfig {
+    // Defaults to `iggy.stream`
+    #[serde(default)]
+    pub stream: Option<String>,
+    #[serde(default = "default_consumer_request_topic")]
+    pub request_topic: String,
+    #[serde(default = "default_consumer_response_topic")]
+    pub response_topic: String,
+    // Workers sharing a group split the requests between them
+    #[serde(default = "default_consumer_group")]
+    pub group: String,
+    // Language of requests that don't carry one
+    #[serde(default = "default_consumer_language")]
+    pub language: String,
+    // Dispatch the action of every complete match, like the CLI does
+    #[serde(default = "default_consumer_dispatch_actions")]
+    pub dispatch_actions: bool,
+}
+
+impl Default for ConsumerConfig {
+    fn default() -> Self {
+        Self {
+            stream: None,
+            request_topic: default_consumer_request_topic(),
+            response_topic: default_consumer_response_topic(),
+            group: default_consumer_group(),
+            language: default_consumer_language(),
+            dispatch_actions: default_consumer_dispatch_actions(),
+        }
+    }
+}
+
+fn default_consumer_request_topic() -> String {
+    "match-requests".to_string()
+}
+
+fn default_consumer_response_topic() -> String {
+    "match-results".to_string()
+}
+
+fn default_consumer_group() -> String {
+    "matcher".to_string()
+}
+
+fn default_consumer_language() -> String {
+    DEFAULT_LANGUAGE.to_string()
+}
+
+fn default_consumer_dispatch_actions() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionStoreKind {
+    #[default]
+    Memory,
+    // One JSON file per session under `sessions.path`
+    File,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SessionsConfig {
+    #[serde(default)]
+    pub store: SessionStoreKind,
+    #[serde(default = "default_sessions_path")]
+    pub path: String,
+    // Interactive sessions without activity for longer than this are dropped
+    #[serde(default = "default_session_idle_secs")]
+    pub idle_timeout_secs: u64,
+    // Abandon the session when the client takes longer than this to answer
+    // a confirmation or parameter prompt, 0 waits forever
+    #[serde(default = "default_confirmation_timeout_secs")]
+    pub confirmation_timeout_secs: u64,
+    #[serde(default = "default_parameter_timeout_secs")]
+    pub parameter_timeout_secs: u64,
+}
+
+impl Default for SessionsConfig {
+    fn default() -> Self {
+        Self {
+            store: SessionStoreKind::default(),
+            path: default_sessions_path(),
+            idle_timeout_secs: default_session_idle_secs(),
+            confirmation_timeout_secs: default_confirmation_timeout_secs(),
+            parameter_timeout_secs: default_parameter_timeout_secs(),
+        }
+    }
+}
+
+fn default_sessions_path() -> String {
+    "data/sessions".to_string()
+}
+
+fn default_session_idle_secs() -> u64 {
+    900
+}
+
+fn default_confirmation_timeout_secs() -> u64 {
+    120
+}
+
+fn default_parameter_timeout_secs() -> u64 {
+    300
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ContextConfig {
+    // Keep the last match of each session so that follow-ups can inherit it
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    // Below this similarity a query carrying parameters is read as a follow-up
+    #[serde(default = "default_follow_up_threshold")]
+    pub follow_up_threshold: f32,
+    // Sessions idle for longer than this are forgotten
+    #[serde(default = "default_context_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            follow_up_threshold: default_follow_up_threshold(),
+            ttl_secs: default_context_ttl_secs(),
+        }
+    }
+}
+
+// Last match of a session
+#[derive(Debug, Clone)]
+pub struct ConversationContext {
+    pub endpoint_id: String,
+    pub parameters: HashMap<String, String>,
+    pub similarity: f32,
+    pub confidence: f32,
+}
+
+fn default_follow_up_threshold() -> f32 {
+    0.6
+}
+
+fn default_context_ttl_secs() -> u64 {
+    1800
+}
+
+fn default_max_edit_distance() -> usize {
+    2
+}
+
+fn default_aggregation_top_k() -> usize {
+    3
+}
+
+fn default_softmax_temperature() -> f32 {
+    0.05
+}
+
+fn default_overfetch_factor() -> usize {
+    4
+}
+
+fn default_vector_weight() -> f32 {
+    0.7
+}
+
+fn default_rrf_k() -> f32 {
+    60.0
+}
+
+fn default_language_boost() -> f32 {
+    0.05
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Config {
     #[serde(default)]
     pub endpoints: Vec<Endpoint>,
+    #[serde(default)]
+    pub search: SearchConfig,
+    #[serde(default)]
+    pub preprocessing: PreprocessingConfig,
+    #[serde(default)]
+    pub rerank: RerankConfig,
+    #[serde(default)]
+    pub negatives: NegativesConfig,
+    #[serde(default)]
+    pub context: ContextConfig,
+    #[serde(default)]
+    pub sessions: SessionsConfig,
+    #[serde(default)]
+    pub actions: ActionsConfig,
+    #[serde(default)]
+    pub iggy: IggyConfig,
+    #[serde(default)]
+    pub consumer: ConsumerConfig,
+    // Shared broker connection, created on first use from `iggy`
+    #[serde(skip)]
+    pub(crate) iggy_pool: OnceLock<Arc<IggyPool>>,
+    #[serde(skip)]
+    pub(crate) outbox: OnceLock<Arc<Outbox>>,
+    // Connection pool shared by the webhook dispatchers
+    #[serde(skip)]
+    pub(crate) http_client: OnceLock<reqwest::Client>,
+    // Built on first use from the configured patterns, one per language
+    #[serde(skip)]
+    pub(crate) spell_correctors: OnceLock<HashMap<String, SpellCorrector>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PatternsDefinition {
+    List(Vec<String>),
+    ByLanguage(BTreeMap<String, Vec<String>>),
+}
+
+fn deserialize_patterns<'de, D>(deserializer: D) -> Result<BTreeMap<String, Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match PatternsDefinition::deserialize(deserializer)? {
+        PatternsDefinition::List(patterns) => {
+            BTreeMap::from([(DEFAULT_LANGUAGE.to_string(), patterns)])
+        }
+        PatternsDefinition::ByLanguage(patterns) => patterns,
+    })
+}
+
+fn flatten_patterns(
+    patterns: &BTreeMap<String, Vec<String>>,
+) -> impl Iterator<Item = (&str, &str)> {
+    patterns.iter().flat_map(|(language, patterns)| {
+        patterns
+            .iter()
+            .map(move |pattern| (language.as_str(), pattern.as_str()))
+    })
 }
 
 impl Endpoint {
     // Helper method to validate patterns
     pub fn validate(&self) -> Result<(), String> {
         // Check if we have at least one pattern
-        if self.patterns.is_empty() {
+        if self.patterns.values().all(|p| p.is_empty()) {
             return Err(format!("Endpoint {} has no patterns defined", self.id));
         }
 
-        // Validate each pattern contains parameter placeholders if parameters are defined
-        for param in &self.parameters {
-            if param.required {
-                let placeholder = format!("{{{}}}", param.name);
-                if !self.patterns.iter().any(|p| p.contains(&placeholder)) {
-                    return Err(format!(
-                        "Required parameter {} not found in any pattern for endpoint {}",
-                        param.name, self.id
-                    ));
+        // Validate each language contains parameter placeholders if parameters are defined
+        for (language, patterns) in &self.patterns {
+            for param in &self.parameters {
+                if param.required {
+                    let placeholder = format!("{{{}}}", param.name);
+                    if !patterns.iter().any(|p| p.contains(&placeholder)) {
+                        return Err(format!(
+                            "Required parameter {} not found in any '{}' pattern for endpoint {}",
+                            param.name, language, self.id
+                        ));
+                    }
                 }
             }
         }
         Ok(())
     }
+
+    // Iterates over (language, pattern) pairs
+    pub fn all_patterns(&self) -> impl Iterator<Item = (&str, &str)> {
+        flatten_patterns(&self.patterns)
+    }
+
+    pub fn all_negative_patterns(&self) -> impl Iterator<Item = (&str, &str)> {
+        flatten_patterns(&self.negative_patterns)
+    }
 }
 
 impl Config {
+    pub fn threshold_for(&self, endpoint_id: &str) -> f32 {
+        self.endpoints
+            .iter()
+            .find(|e| e.id == endpoint_id)
+            .and_then(|e| e.threshold)
+            .unwrap_or(self.search.threshold)
+    }
+
+    /// Copy with every threshold and the ambiguity margin disabled, so that
+    /// all candidates are returned when tuning them.
+    pub fn without_thresholds(&self) -> Self {
+        let mut config = self.clone();
+        config.search.threshold = 0.0;
+        config.search.ambiguity_margin = 0.0;
+        for endpoint in &mut config.endpoints {
+            endpoint.threshold = None;
+        }
+        config
+    }
+
+    pub(crate) fn spell_corrector(&self, language: &str) -> Option<&SpellCorrector> {
+        if !self.preprocessing.spell_correction {
+            return None;
+        }
+
+        self.spell_correctors
+            .get_or_init(|| {
+                let mut languages: Vec<&str> = self
+                    .endpoints
+                    .iter()
+                    .flat_map(|endpoint| endpoint.patterns.keys())
+                    .map(String::as_str)
+                    .collect();
+                languages.sort_unstable();
+                languages.dedup();
+
+                languages
+                    .into_iter()
+                    .map(|language| {
+                        (
+                            language.to_string(),
+                            SpellCorrector::from_config(self, language),
+                        )
+                    })
+                    .collect()
+            })
+            .get(language)
+    }
+
+    pub fn iggy_pool(&self) -> &Arc<IggyPool> {
+        self.iggy_pool
+            .get_or_init(|| Arc::new(IggyPool::new(self.iggy.clone())))
+    }
+
+    pub fn outbox(&self) -> &Arc<Outbox> {
+        self.outbox
+            .get_or_init(|| Arc::new(Outbox::new(&self.actions.outbox)))
+    }
+
+    pub fn http_client(&self) -> &reqwest::Client {
+        self.http_client.get_or_init(reqwest::Client::new)
+    }
+
     pub fn load_from_yaml<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
         let f = std::fs::File::open(path)?;
-        let config: Config = serde_yaml::from_reader(f)?;
+        let mut config: Config = serde_yaml::from_reader(f)?;
+        config.iggy.apply_env_overrides()?;
 
         // Validate all endpoints
         for endpoint in &config.endpoints {
             endpoint.validate().map_err(|e| anyhow::anyhow!(e))?;
+            if let NegationPolicy::Inverse { endpoint: inverse } = &endpoint.negation {
+                if !config.endpoints.iter().any(|e| &e.id == inverse) {
+                    return Err(anyhow::anyhow!(
+                        "Endpoint {} declares unknown inverse endpoint {}",
+                        endpoint.id,
+                        inverse
+                    ));
+                }
+            }
         }
 
+        ThresholdOverrides::apply(&mut config, path)?;
         Ok(config)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patterns_by_language() {
+        let yaml = r#"
+endpoints:
+  - id: "send_email"
+    text: "envoyer email"
+    description: "Envoyer un email"
+    patterns:
+      fr:
+        - "envoyer un mail à {email}"
+      en:
+        - "send an email to {email}"
+    parameters:
+      - name: "email"
+        description: "Adresse email"
+        required: true
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let endpoint = &config.endpoints[0];
+        assert!(endpoint.validate().is_ok());
+        assert_eq!(
+            endpoint.all_patterns().collect::<Vec<_>>(),
+            vec![
+                ("en", "send an email to {email}"),
+                ("fr", "envoyer un mail à {email}")
+            ]
+        );
+        assert_eq!(config.search.language_mode, LanguageMode::Filter);
+    }
+
+    #[test]
+    fn test_plain_pattern_list_uses_default_language() {
+        let yaml = r#"
+endpoints:
+  - id: "run_analysis"
+    text: "lancer analyse"
+    description: "Lancer une analyse"
+    patterns:
+      - "lancer analyse"
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            config.endpoints[0].patterns.get(DEFAULT_LANGUAGE),
+            Some(&vec!["lancer analyse".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_missing_placeholder_in_one_language() {
+        let yaml = r#"
+endpoints:
+  - id: "send_email"
+    text: "envoyer email"
+    description: "Envoyer un email"
+    patterns:
+      fr: ["envoyer un mail à {email}"]
+      en: ["send an email"]
+    parameters:
+      - name: "email"
+        description: "Adresse email"
+        required: true
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.endpoints[0].validate().is_err());
+    }
+
+    #[test]
+    fn test_iggy_overrides_and_secret_files() {
+        let secret = std::env::temp_dir().join(format!("matcher-iggy-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&secret, "s3cret\n").unwrap();
+        let overrides = HashMap::from([
+            ("TRANSPORT", "quic".to_string()),
+            ("ADDRESS", "localhost:8080".to_string()),
+            ("PASSWORD_FILE", secret.display().to_string()),
+        ]);
+
+        let mut iggy = IggyConfig::default();
+        iggy.apply_overrides(|name| overrides.get(name).cloned())
+            .unwrap();
+        assert_eq!(iggy.transport, IggyTransport::Quic);
+        assert_eq!(iggy.address, "localhost:8080");
+        assert_eq!(
+            iggy.credentials().unwrap(),
+            ("iggy".to_string(), "s3cret".to_string())
+        );
+        std::fs::remove_file(secret).unwrap();
+    }
+}
@@ -1 +1,3 @@
 pub const CONFIG_PATH: &str = "endpoints.yaml";
+pub const DEFAULT_LANGUAGE: &str = "fr";
+pub const DB_PATH: &str = "data/mydb";
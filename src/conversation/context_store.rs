@@ -0,0 +1,33 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::ConversationContext;
+
+/// Last match of each session, forgotten after `ttl` without activity.
+pub struct ContextStore {
+    ttl: Duration,
+    contexts: Mutex<HashMap<String, (Instant, ConversationContext)>>,
+}
+
+impl ContextStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            contexts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, session_id: &str) -> Option<ConversationContext> {
+        let mut contexts = self.contexts.lock().unwrap();
+        contexts.retain(|_, (updated_at, _)| updated_at.elapsed() < self.ttl);
+        contexts.get(session_id).map(|(_, context)| context.clone())
+    }
+
+    pub fn set(&self, session_id: &str, context: ConversationContext) {
+        self.contexts
+            .lock()
+            .unwrap()
+            .insert(session_id.to_string(), (Instant::now(), context));
+    }
+}
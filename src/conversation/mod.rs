@@ -0,0 +1,2 @@
+pub mod context_store;
+pub mod resolve_follow_up;
@@ -0,0 +1,251 @@
+use tracing::debug;
+
+use crate::config::{Config, ConversationContext, SearchOutcome, SearchResult};
+use crate::preprocessing::language_patterns::FOLLOW_UP_REGEXES;
+use crate::preprocessing::preprocess_query::preprocess_query;
+
+/// When `clause` continues the previous request of the session, replaces the
+/// results with the previous endpoint. The values the clause mentions are
+/// merged over its previous parameters. Returns whether the context was applied.
+///
+/// A clause is a follow-up when it matches no endpoint well (below the
+/// follow-up threshold, or no result left once ambiguous matches were
+/// dropped) and either has a follow-up marker ("aussi", "instead") or carries
+/// parameters. A confident fresh match always wins over the context.
+///
+/// Only the parameters preprocessing extracts from free text (`email` and
+/// `app`) can be overridden; the others keep their previous value.
+pub fn resolve_follow_up(
+    clause: &str,
+    language: &str,
+    outcome: &mut SearchOutcome,
+    context: &ConversationContext,
+    config: &Config,
+) -> bool {
+    let Some(endpoint) = config
+        .endpoints
+        .iter()
+        .find(|e| e.id == context.endpoint_id)
+    else {
+        return false;
+    };
+
+    let (has_marker, stripped) = strip_follow_up_markers(clause, language);
+    let mentioned = preprocess_query(&stripped, language, config).parameters;
+
+    let weak = outcome
+        .results
+        .first()
+        .map_or(true, |b| b.similarity < config.context.follow_up_threshold);
+    let is_follow_up = weak && (has_marker || !mentioned.is_empty());
+    if !is_follow_up {
+        return false;
+    }
+
+    let mut parameters = context.parameters.clone();
+    for param in &endpoint.parameters {
+        if let Some(value) = mentioned.get(&param.name) {
+            parameters.insert(param.name.clone(), value.clone());
+        }
+    }
+    debug!(
+        "Follow-up of '{}', inherited parameters: {:?}",
+        endpoint.id, parameters
+    );
+
+    let parameter_analysis = endpoint.analyze_parameters(&parameters);
+    outcome.results = vec![SearchResult {
+        endpoint_id: endpoint.id.clone(),
+        pattern: String::new(),
+        language: language.to_string(),
+        similarity: context.similarity,
+        confidence: context.confidence,
+        is_negative: false,
+        parameters,
+        parameter_analysis,
+    }];
+    true
+}
+
+// Whether `clause` contains a follow-up marker, and the clause without them.
+// The casing is kept for the parameter values.
+fn strip_follow_up_markers(clause: &str, language: &str) -> (bool, String) {
+    let mut text = clause.to_string();
+    let Some(regexes) = FOLLOW_UP_REGEXES.get(language) else {
+        return (false, text);
+    };
+
+    let mut has_marker = false;
+    for regex in regexes {
+        if regex.is_match(&text) {
+            has_marker = true;
+            text = regex.replace_all(&text, " ").into_owned();
+        }
+    }
+    (
+        has_marker,
+        text.split_whitespace().collect::<Vec<_>>().join(" "),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Endpoint, Parameter, ParameterAnalysis};
+    use std::collections::{BTreeMap, HashMap};
+
+    fn config() -> Config {
+        let endpoint = |id: &str, param: &str| Endpoint {
+            id: id.to_string(),
+            text: id.to_string(),
+            patterns: BTreeMap::new(),
+            negative_patterns: BTreeMap::new(),
+            description: String::new(),
+            parameters: vec![Parameter {
+                name: param.to_string(),
+                description: String::new(),
+                required: true,
+                kind: Default::default(),
+            }],
+            threshold: None,
+            optional_parameters: Default::default(),
+            action: None,
+            negation: Default::default(),
+        };
+        Config {
+            endpoints: vec![
+                endpoint("analyze_specific_repository", "app"),
+                endpoint("send_email", "email"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn outcome(endpoint_id: &str, similarity: f32) -> SearchOutcome {
+        SearchOutcome {
+            results: vec![SearchResult {
+                endpoint_id: endpoint_id.to_string(),
+                pattern: String::new(),
+                language: "fr".to_string(),
+                similarity,
+                confidence: similarity,
+                is_negative: false,
+                parameters: HashMap::new(),
+                parameter_analysis: ParameterAnalysis {
+                    missing_required: vec![],
+                    missing_optional: vec![],
+                    found: HashMap::new(),
+                },
+            }],
+            best_similarity: similarity,
+            suppressed: vec![],
+            processed: Default::default(),
+        }
+    }
+
+    fn context(endpoint_id: &str, name: &str, value: &str) -> ConversationContext {
+        ConversationContext {
+            endpoint_id: endpoint_id.to_string(),
+            parameters: HashMap::from([(name.to_string(), value.to_string())]),
+            similarity: 0.9,
+            confidence: 0.8,
+        }
+    }
+
+    #[test]
+    fn test_follow_up_overrides_mentioned_parameter() {
+        let config = config();
+        let context = context("analyze_specific_repository", "app", "gpecs");
+        let mut outcome = outcome("send_email", 0.3);
+
+        assert!(resolve_follow_up(
+            "et pour divess aussi",
+            "fr",
+            &mut outcome,
+            &context,
+            &config
+        ));
+        let result = &outcome.results[0];
+        assert_eq!(result.endpoint_id, "analyze_specific_repository");
+        assert_eq!(result.parameters["app"], "divess");
+        assert!(result.parameter_analysis.missing_required.is_empty());
+    }
+
+    #[test]
+    fn test_anaphora_inherits_endpoint() {
+        let config = config();
+        let context = context("send_email", "email", "toto@gmail.com");
+        let mut outcome = outcome("send_email", 0.4);
+
+        assert!(resolve_follow_up(
+            "send it to bob@x.com instead",
+            "en",
+            &mut outcome,
+            &context,
+            &config
+        ));
+        assert_eq!(outcome.results[0].parameters["email"], "bob@x.com");
+    }
+
+    #[test]
+    fn test_new_intent_replaces_context() {
+        let config = config();
+        let context = context("send_email", "email", "toto@gmail.com");
+        let mut outcome = outcome("analyze_specific_repository", 0.85);
+
+        assert!(!resolve_follow_up(
+            "analyse de gpecs",
+            "fr",
+            &mut outcome,
+            &context,
+            &config
+        ));
+        assert_eq!(
+            outcome.results[0].endpoint_id,
+            "analyze_specific_repository"
+        );
+    }
+
+    #[test]
+    fn test_confident_match_ignores_marker() {
+        let config = config();
+        let context = context("send_email", "email", "toto@gmail.com");
+        let mut outcome = outcome("analyze_specific_repository", 0.85);
+
+        assert!(!resolve_follow_up(
+            "analyze it too",
+            "en",
+            &mut outcome,
+            &context,
+            &config
+        ));
+        assert_eq!(
+            outcome.results[0].endpoint_id,
+            "analyze_specific_repository"
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_match_merges_parameters() {
+        let config = config();
+        let mut context = context("send_email", "email", "toto@gmail.com");
+        context
+            .parameters
+            .insert("title".to_string(), "Rapport".to_string());
+        let mut outcome = SearchOutcome {
+            results: vec![],
+            ..outcome("send_email", 0.0)
+        };
+
+        assert!(resolve_follow_up(
+            "and to bob@x.com too",
+            "en",
+            &mut outcome,
+            &context,
+            &config
+        ));
+        let parameters = &outcome.results[0].parameters;
+        assert_eq!(parameters["email"], "bob@x.com");
+        assert_eq!(parameters["title"], "Rapport");
+    }
+}
@@ -9,6 +9,7 @@ pub async fn initialize_table(connection: &Connection, endpoints: &[Endpoint]) -
 
     let config = Config {
         endpoints: endpoints.to_vec(),
+        ..Default::default()
     };
 
     // Force creation of new table by passing config and with_init as true
@@ -6,6 +6,8 @@ lazy_static::lazy_static! {
     pub(crate) static ref PATTERNS_SCHEMA: Schema = Schema::new(vec![
         Field::new("endpoint_id", DataType::Utf8, false),
         Field::new("pattern", DataType::Utf8, false),
+        Field::new("language", DataType::Utf8, false),
+        Field::new("is_negative", DataType::Boolean, false),
         Field::new(
             "vector",
             DataType::FixedSizeList(
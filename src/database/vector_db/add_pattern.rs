@@ -4,14 +4,24 @@ use crate::candle::get_embeddings::get_embeddings;
 use crate::config::Endpoint;
 use anyhow::Result as AnyhowResult;
 use arrow::datatypes::Float32Type;
-use arrow_array::{FixedSizeListArray, RecordBatch, RecordBatchIterator, StringArray};
+use arrow_array::{
+    BooleanArray, FixedSizeListArray, RecordBatch, RecordBatchIterator, StringArray,
+};
 use std::sync::Arc;
 
 impl VectorDB {
-    pub async fn add_pattern(&self, endpoint_id: &str, pattern: &str) -> AnyhowResult<()> {
+    pub async fn add_pattern(
+        &self,
+        endpoint_id: &str,
+        language: &str,
+        pattern: &str,
+        is_negative: bool,
+    ) -> AnyhowResult<()> {
         let embedding = get_embeddings(pattern).await?;
         let id_array = Arc::new(StringArray::from(vec![endpoint_id]));
         let pattern_array = Arc::new(StringArray::from(vec![pattern]));
+        let language_array = Arc::new(StringArray::from(vec![language]));
+        let negative_array = Arc::new(BooleanArray::from(vec![is_negative]));
         let vector_array = Arc::new(
             FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
                 vec![Some(
@@ -22,7 +32,13 @@ impl VectorDB {
         );
         let pattern_batch = RecordBatch::try_new(
             self.patterns_schema.clone(),
-            vec![id_array, pattern_array, vector_array],
+            vec![
+                id_array,
+                pattern_array,
+                language_array,
+                negative_array,
+                vector_array,
+            ],
         )?;
 
         let batch_iterator =
@@ -38,9 +54,15 @@ impl VectorDB {
     pub(crate) async fn add_patterns(&self, endpoints: &[Endpoint]) -> AnyhowResult<()> {
         for endpoint in endpoints {
             println!("\nProcessing endpoint: {}", endpoint.id);
-            for pattern in &endpoint.patterns {
-                println!("  Adding pattern: '{}'", pattern);
-                self.add_pattern(&endpoint.id, pattern).await?;
+            for (language, pattern) in endpoint.all_patterns() {
+                println!("  Adding {} pattern: '{}'", language, pattern);
+                self.add_pattern(&endpoint.id, language, pattern, false)
+                    .await?;
+            }
+            for (language, pattern) in endpoint.all_negative_patterns() {
+                println!("  Adding {} negative pattern: '{}'", language, pattern);
+                self.add_pattern(&endpoint.id, language, pattern, true)
+                    .await?;
             }
         }
         Ok(())
@@ -1,16 +1,22 @@
 use super::super::schema::PATTERNS_SCHEMA;
+use crate::calibration::Calibration;
+use crate::candle::MODEL_PATH;
 use crate::config::Config;
 use anyhow::Result as AnyhowResult;
 use arrow_array::{RecordBatch, RecordBatchIterator};
 use arrow_schema::Schema;
+use lancedb::index::scalar::FtsIndexBuilder;
+use lancedb::index::Index;
 use lancedb::{connect, Connection, Table};
 use std::sync::Arc;
+use tracing::warn;
 
 pub struct VectorDB {
     #[allow(dead_code)]
     pub(crate) connection: Connection,
     pub(crate) patterns_table: Table,
     pub(crate) patterns_schema: Arc<Schema>,
+    pub(crate) calibration: Option<Calibration>,
 }
 
 impl VectorDB {
@@ -41,12 +47,20 @@ impl VectorDB {
                 connection: connection.clone(),
                 patterns_table: table,
                 patterns_schema: Arc::new(PATTERNS_SCHEMA.clone()),
+                calibration: None,
             };
 
             // Initialize with patterns if config is provided
             if let Some(cfg) = config {
                 println!("Initializing patterns...");
                 db.add_patterns(&cfg.endpoints).await?;
+
+                // Full-text index on the pattern column, used by hybrid search
+                println!("Creating full-text index on patterns...");
+                db.patterns_table
+                    .create_index(&["pattern"], Index::FTS(FtsIndexBuilder::default()))
+                    .execute()
+                    .await?;
             }
 
             db.patterns_table
@@ -62,10 +76,26 @@ impl VectorDB {
             }
         };
 
+        let calibration = match Calibration::load(connection.uri()) {
+            Ok(Some(calibration)) if calibration.model != MODEL_PATH => {
+                warn!(
+                    "Ignoring calibration fitted for model '{}', run calibrate again",
+                    calibration.model
+                );
+                None
+            }
+            Ok(calibration) => calibration,
+            Err(e) => {
+                warn!("Failed to load calibration, using raw similarities: {}", e);
+                None
+            }
+        };
+
         Ok(Self {
             connection,
             patterns_table,
             patterns_schema: Arc::new(PATTERNS_SCHEMA.clone()),
+            calibration,
         })
     }
 }
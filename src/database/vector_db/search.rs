@@ -1,7 +1,9 @@
 use super::db::VectorDB;
-use crate::config::{Config, SearchResult};
+use crate::calibration::ScoringSettings;
+use crate::config::{Config, SearchOptions, SearchOutcome, SearchResult};
 use crate::search_operations::search_similar;
 use anyhow::Result as AnyhowResult;
+use tracing::debug;
 
 impl VectorDB {
     pub async fn search_similar(
@@ -11,6 +13,50 @@ impl VectorDB {
         limit: usize,
         config: &Config,
     ) -> AnyhowResult<(Vec<SearchResult>, f32)> {
-        search_similar(&self.patterns_table, query, language, limit, config).await
+        let outcome = self
+            .search_similar_with_options(query, language, limit, config, &SearchOptions::default())
+            .await?;
+        Ok((outcome.results, outcome.best_similarity))
+    }
+
+    pub async fn search_similar_with_options(
+        &self,
+        query: &str,
+        language: &str,
+        limit: usize,
+        config: &Config,
+        options: &SearchOptions,
+    ) -> AnyhowResult<SearchOutcome> {
+        let mut outcome = search_similar(
+            &self.patterns_table,
+            query,
+            language,
+            limit,
+            config,
+            options,
+        )
+        .await?;
+
+        // A calibration only holds for the scoring it was fitted on
+        let scoring = ScoringSettings::resolve(config, options);
+        let calibration = self
+            .calibration
+            .as_ref()
+            .filter(|calibration| calibration.scoring == scoring);
+        if calibration.is_none() && self.calibration.is_some() {
+            debug!(
+                "Calibration was fitted for {:?}, search uses {:?}: using raw similarities",
+                self.calibration.as_ref().map(|c| c.scoring),
+                scoring
+            );
+        }
+
+        for result in &mut outcome.results {
+            result.confidence = match calibration {
+                Some(calibration) => calibration.apply(result.similarity),
+                None => result.similarity.clamp(0.0, 1.0),
+            };
+        }
+        Ok(outcome)
     }
 }
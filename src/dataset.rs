@@ -0,0 +1,21 @@
+use anyhow::{Context, Result as AnyhowResult};
+use std::path::Path;
+
+use crate::config::LabelledQuery;
+
+/// Reads a JSONL file of labelled queries, skipping blank lines.
+pub fn load_labelled_queries<P: AsRef<Path>>(path: P) -> AnyhowResult<Vec<LabelledQuery>> {
+    let path = path.as_ref();
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Invalid labelled query at {:?}:{}", path, idx + 1))
+        })
+        .collect()
+}
@@ -0,0 +1,269 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+// Confusion matrix label of queries that match, or should match, nothing
+pub const NO_MATCH: &str = "(none)";
+
+/// A labelled query and what the pipeline returned for it.
+#[derive(Debug, Clone)]
+pub struct EvaluationSample {
+    pub query: String,
+    pub language: String,
+    pub expected: Option<String>,
+    // Returned endpoints with their similarity, best first
+    pub candidates: Vec<(String, f32)>,
+    // Extraction and negation of the best match
+    pub parameters: BTreeMap<String, String>,
+    pub negated: bool,
+    pub expected_parameters: Option<BTreeMap<String, String>>,
+    pub expected_negated: Option<bool>,
+}
+
+impl EvaluationSample {
+    pub fn predicted(&self) -> Option<&str> {
+        self.candidates.first().map(|(id, _)| id.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct EndpointMetrics {
+    // Queries labelled with the endpoint
+    pub support: usize,
+    // Queries whose best match is the endpoint
+    pub predicted: usize,
+    pub true_positives: usize,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RateMetrics {
+    // Queries labelled for this metric
+    pub evaluated: usize,
+    pub correct: usize,
+    pub rate: f64,
+}
+
+impl RateMetrics {
+    fn new(outcomes: impl Iterator<Item = bool>) -> Self {
+        let (evaluated, correct) = outcomes.fold((0, 0), |(evaluated, correct), ok| {
+            (evaluated + 1, correct + ok as usize)
+        });
+        Self {
+            evaluated,
+            correct,
+            rate: ratio(correct, evaluated),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Misclassification {
+    pub query: String,
+    pub language: String,
+    pub expected: String,
+    pub predicted: String,
+    pub similarity: Option<f32>,
+}
+
+/// Metrics of one evaluation run. Maps are ordered and the report holds no
+/// timestamp, so reports of two catalog versions can be diffed.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct EvaluationReport {
+    pub queries: usize,
+    pub top_k: usize,
+    pub top1_accuracy: f64,
+    pub top_k_accuracy: f64,
+    pub macro_f1: f64,
+    pub endpoints: BTreeMap<String, EndpointMetrics>,
+    // Expected endpoint -> best match -> count
+    pub confusion_matrix: BTreeMap<String, BTreeMap<String, usize>>,
+    // The labelled parameters are exactly the extracted ones
+    pub parameter_exact_match: RateMetrics,
+    pub negation_accuracy: RateMetrics,
+    pub misclassified: Vec<Misclassification>,
+}
+
+impl EvaluationReport {
+    pub fn from_samples(samples: &[EvaluationSample], top_k: usize) -> Self {
+        let label = |id: Option<&str>| id.unwrap_or(NO_MATCH).to_string();
+
+        // Out-of-scope queries are correct in the top k only when nothing matched
+        let top1 = RateMetrics::new(
+            samples
+                .iter()
+                .map(|s| s.predicted() == s.expected.as_deref()),
+        );
+        let top_k_hits = RateMetrics::new(samples.iter().map(|s| {
+            match &s.expected {
+                Some(expected) => s
+                    .candidates
+                    .iter()
+                    .take(top_k)
+                    .any(|(id, _)| id == expected),
+                None => s.candidates.is_empty(),
+            }
+        }));
+
+        let mut confusion_matrix: BTreeMap<String, BTreeMap<String, usize>> = BTreeMap::new();
+        for sample in samples {
+            *confusion_matrix
+                .entry(label(sample.expected.as_deref()))
+                .or_default()
+                .entry(label(sample.predicted()))
+                .or_default() += 1;
+        }
+
+        let endpoint_ids: BTreeSet<&str> = samples
+            .iter()
+            .flat_map(|s| [s.expected.as_deref(), s.predicted()])
+            .flatten()
+            .collect();
+        let endpoints: BTreeMap<String, EndpointMetrics> = endpoint_ids
+            .into_iter()
+            .map(|id| {
+                let support = samples
+                    .iter()
+                    .filter(|s| s.expected.as_deref() == Some(id))
+                    .count();
+                let predicted = samples.iter().filter(|s| s.predicted() == Some(id)).count();
+                let true_positives = samples
+                    .iter()
+                    .filter(|s| s.expected.as_deref() == Some(id) && s.predicted() == Some(id))
+                    .count();
+                let precision = ratio(true_positives, predicted);
+                let recall = ratio(true_positives, support);
+                let f1 = if precision + recall > 0.0 {
+                    2.0 * precision * recall / (precision + recall)
+                } else {
+                    0.0
+                };
+                let metrics = EndpointMetrics {
+                    support,
+                    predicted,
+                    true_positives,
+                    precision,
+                    recall,
+                    f1,
+                };
+                (id.to_string(), metrics)
+            })
+            .collect();
+        // Averaged over the endpoints present in the dataset
+        let labelled: Vec<&EndpointMetrics> =
+            endpoints.values().filter(|m| m.support > 0).collect();
+        let macro_f1 = if labelled.is_empty() {
+            0.0
+        } else {
+            labelled.iter().map(|m| m.f1).sum::<f64>() / labelled.len() as f64
+        };
+
+        let parameter_exact_match = RateMetrics::new(samples.iter().filter_map(|s| {
+            s.expected_parameters
+                .as_ref()
+                .map(|expected| s.predicted() == s.expected.as_deref() && *expected == s.parameters)
+        }));
+        let negation_accuracy = RateMetrics::new(
+            samples
+                .iter()
+                .filter_map(|s| s.expected_negated.map(|expected| expected == s.negated)),
+        );
+
+        let misclassified = samples
+            .iter()
+            .filter(|s| s.predicted() != s.expected.as_deref())
+            .map(|s| Misclassification {
+                query: s.query.clone(),
+                language: s.language.clone(),
+                expected: label(s.expected.as_deref()),
+                predicted: label(s.predicted()),
+                similarity: s.candidates.first().map(|(_, similarity)| *similarity),
+            })
+            .collect();
+
+        Self {
+            queries: samples.len(),
+            top_k,
+            top1_accuracy: top1.rate,
+            top_k_accuracy: top_k_hits.rate,
+            macro_f1,
+            endpoints,
+            confusion_matrix,
+            parameter_exact_match,
+            negation_accuracy,
+            misclassified,
+        }
+    }
+}
+
+/// `numerator / denominator`, or 0 when there is nothing to divide by.
+pub fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+/// Sample expecting `expected`, with the given ranked candidates.
+#[cfg(test)]
+pub(crate) fn test_sample(expected: Option<&str>, candidates: &[(&str, f32)]) -> EvaluationSample {
+    EvaluationSample {
+        query: "query".to_string(),
+        language: "fr".to_string(),
+        expected: expected.map(str::to_string),
+        candidates: candidates
+            .iter()
+            .map(|(id, similarity)| (id.to_string(), *similarity))
+            .collect(),
+        parameters: BTreeMap::new(),
+        negated: false,
+        expected_parameters: None,
+        expected_negated: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_sample as sample;
+    use super::*;
+
+    #[test]
+    fn test_report_metrics() {
+        let mut with_parameters = sample(Some("send_email"), &[("send_email", 0.9)]);
+        with_parameters.parameters = BTreeMap::from([("email".to_string(), "a@b.ch".to_string())]);
+        with_parameters.expected_parameters = Some(with_parameters.parameters.clone());
+        with_parameters.expected_negated = Some(true);
+
+        let samples = vec![
+            with_parameters,
+            sample(
+                Some("send_email"),
+                &[("start_app", 0.7), ("send_email", 0.6)],
+            ),
+            sample(Some("start_app"), &[("start_app", 0.8)]),
+            sample(None, &[]),
+            sample(None, &[("start_app", 0.5)]),
+        ];
+        let report = EvaluationReport::from_samples(&samples, 5);
+
+        assert_eq!(report.queries, 5);
+        assert!((report.top1_accuracy - 0.6).abs() < 1e-9);
+        assert!((report.top_k_accuracy - 0.8).abs() < 1e-9);
+
+        let send_email = &report.endpoints["send_email"];
+        assert_eq!((send_email.support, send_email.predicted), (2, 1));
+        assert!((send_email.precision - 1.0).abs() < 1e-9);
+        assert!((send_email.recall - 0.5).abs() < 1e-9);
+        let start_app = &report.endpoints["start_app"];
+        assert!((start_app.precision - 1.0 / 3.0).abs() < 1e-9);
+
+        assert_eq!(report.confusion_matrix[NO_MATCH]["start_app"], 1);
+        assert_eq!(report.confusion_matrix["send_email"]["start_app"], 1);
+        assert_eq!(report.parameter_exact_match.correct, 1);
+        assert_eq!(report.negation_accuracy.evaluated, 1);
+        assert_eq!(report.negation_accuracy.correct, 0);
+        assert_eq!(report.misclassified.len(), 2);
+    }
+}
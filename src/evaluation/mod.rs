@@ -0,0 +1,4 @@
+pub mod metrics;
+pub mod report;
+pub mod run_evaluation;
+pub mod tune_thresholds;
@@ -0,0 +1,116 @@
+use std::fmt::Write;
+
+use super::metrics::{EvaluationReport, RateMetrics};
+
+impl EvaluationReport {
+    /// Human-readable version of the report, stable between runs.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let rate = |metrics: &RateMetrics| {
+            format!(
+                "{} ({}/{})",
+                percent(metrics.rate),
+                metrics.correct,
+                metrics.evaluated
+            )
+        };
+
+        // Writing to a String cannot fail
+        let _ = writeln!(out, "# Evaluation report\n");
+        let _ = writeln!(out, "| Metric | Value |");
+        let _ = writeln!(out, "|---|---|");
+        let _ = writeln!(out, "| Queries | {} |", self.queries);
+        let _ = writeln!(out, "| Top-1 accuracy | {} |", percent(self.top1_accuracy));
+        let _ = writeln!(
+            out,
+            "| Top-{} accuracy | {} |",
+            self.top_k,
+            percent(self.top_k_accuracy)
+        );
+        let _ = writeln!(out, "| Macro F1 | {:.3} |", self.macro_f1);
+        let _ = writeln!(
+            out,
+            "| Parameter exact match | {} |",
+            rate(&self.parameter_exact_match)
+        );
+        let _ = writeln!(
+            out,
+            "| Negation accuracy | {} |",
+            rate(&self.negation_accuracy)
+        );
+
+        let _ = writeln!(out, "\n## Endpoints\n");
+        let _ = writeln!(
+            out,
+            "| Endpoint | Support | Predicted | Precision | Recall | F1 |"
+        );
+        let _ = writeln!(out, "|---|---|---|---|---|---|");
+        for (id, metrics) in &self.endpoints {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {:.3} | {:.3} | {:.3} |",
+                id,
+                metrics.support,
+                metrics.predicted,
+                metrics.precision,
+                metrics.recall,
+                metrics.f1
+            );
+        }
+
+        // Rows are the expected endpoints, columns the best matches
+        let mut predicted: Vec<&String> = self
+            .confusion_matrix
+            .values()
+            .flat_map(|row| row.keys())
+            .collect();
+        predicted.sort();
+        predicted.dedup();
+        let _ = writeln!(out, "\n## Confusion matrix\n");
+        let _ = writeln!(
+            out,
+            "| Expected \\ Predicted | {} |",
+            predicted
+                .iter()
+                .map(|id| id.as_str())
+                .collect::<Vec<_>>()
+                .join(" | ")
+        );
+        let _ = writeln!(out, "|---|{}", "---|".repeat(predicted.len()));
+        for (expected, row) in &self.confusion_matrix {
+            let counts: Vec<String> = predicted
+                .iter()
+                .map(|id| row.get(*id).copied().unwrap_or(0).to_string())
+                .collect();
+            let _ = writeln!(out, "| {} | {} |", expected, counts.join(" | "));
+        }
+
+        let _ = writeln!(out, "\n## Misclassified queries\n");
+        if self.misclassified.is_empty() {
+            let _ = writeln!(out, "None.");
+        } else {
+            let _ = writeln!(
+                out,
+                "| Query | Language | Expected | Predicted | Similarity |"
+            );
+            let _ = writeln!(out, "|---|---|---|---|---|");
+            for miss in &self.misclassified {
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {} | {} | {} |",
+                    miss.query.replace('|', "\\|"),
+                    miss.language,
+                    miss.expected,
+                    miss.predicted,
+                    miss.similarity
+                        .map_or("-".to_string(), |similarity| format!("{:.3}", similarity))
+                );
+            }
+        }
+        out
+    }
+}
+
+fn percent(rate: f64) -> String {
+    format!("{:.1}%", rate * 100.0)
+}
@@ -0,0 +1,76 @@
+use anyhow::{anyhow, Result as AnyhowResult};
+use std::sync::Arc;
+use tonic::Request;
+
+use super::metrics::{EvaluationReport, EvaluationSample};
+use crate::config::{Config, LabelledQuery};
+use crate::constants::DEFAULT_LANGUAGE;
+use crate::database::vector_db::VectorDB;
+use crate::grpc::matcher_service::matcher::matcher_server::Matcher;
+use crate::grpc::matcher_service::matcher::MatchRequest;
+use crate::grpc::matcher_service::{MatcherService, ALL_MATCHES_LIMIT};
+use crate::preprocessing::preprocess_query::preprocess_query;
+
+/// Runs every labelled query through the match pipeline, as the gRPC
+/// service does but without splitting intents, and reports the metrics.
+pub async fn run_evaluation(
+    db: Arc<VectorDB>,
+    config: Arc<Config>,
+    queries: &[LabelledQuery],
+) -> AnyhowResult<EvaluationReport> {
+    let samples = collect_samples(db, config, queries).await?;
+    Ok(EvaluationReport::from_samples(&samples, ALL_MATCHES_LIMIT))
+}
+
+pub async fn collect_samples(
+    db: Arc<VectorDB>,
+    config: Arc<Config>,
+    queries: &[LabelledQuery],
+) -> AnyhowResult<Vec<EvaluationSample>> {
+    let service = MatcherService::new(config, db);
+    let mut samples = Vec::with_capacity(queries.len());
+    for labelled in queries {
+        let language = labelled
+            .language
+            .clone()
+            .unwrap_or_else(|| DEFAULT_LANGUAGE.to_string());
+        let request = MatchRequest {
+            query: labelled.query.clone(),
+            language: language.clone(),
+            show_all_matches: true,
+            split_intents: Some(false),
+            ..Default::default()
+        };
+        let response = service
+            .match_query(Request::new(request))
+            .await
+            .map_err(|status| {
+                anyhow!("Failed to match '{}': {}", labelled.query, status.message())
+            })?
+            .into_inner();
+
+        let best = response.matches.first();
+        // Negation is also evaluated on queries that matched nothing
+        let negated = best.map_or_else(
+            || preprocess_query(&labelled.query, &language, &service.config).is_negated,
+            |m| m.is_negated,
+        );
+        samples.push(EvaluationSample {
+            query: labelled.query.clone(),
+            language,
+            expected: labelled.endpoint.clone(),
+            candidates: response
+                .matches
+                .iter()
+                .map(|m| (m.endpoint_id.clone(), m.similarity as f32))
+                .collect(),
+            parameters: best
+                .map(|m| m.parameters.clone().into_iter().collect())
+                .unwrap_or_default(),
+            negated,
+            expected_parameters: labelled.parameters.clone(),
+            expected_negated: labelled.negated,
+        });
+    }
+    Ok(samples)
+}
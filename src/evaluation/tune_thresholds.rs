@@ -0,0 +1,477 @@
+use anyhow::{anyhow, Context, Result as AnyhowResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::warn;
+
+use super::metrics::{ratio, EvaluationSample};
+use super::run_evaluation::collect_samples;
+use crate::calibration::ScoringSettings;
+use crate::config::{Config, LabelledQuery, SearchOptions};
+use crate::database::vector_db::VectorDB;
+
+// Largest ambiguity margin tried by the sweep
+const MAX_MARGIN: f64 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TuningTarget {
+    // Answer as many queries as possible at this precision or better
+    Precision(f64),
+    // Answer this share of the in-scope queries as precisely as possible
+    Coverage(f64),
+}
+
+/// Thresholds and margin applied to the untuned candidates of a dataset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Thresholds {
+    pub threshold: f32,
+    pub ambiguity_margin: f32,
+    // Endpoints whose own threshold replaces the global one
+    #[serde(default)]
+    pub endpoints: BTreeMap<String, f32>,
+}
+
+impl Thresholds {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            threshold: config.search.threshold,
+            ambiguity_margin: config.search.ambiguity_margin,
+            endpoints: config
+                .endpoints
+                .iter()
+                .filter_map(|e| e.threshold.map(|threshold| (e.id.clone(), threshold)))
+                .collect(),
+        }
+    }
+
+    fn threshold_for(&self, endpoint_id: &str) -> f32 {
+        self.endpoints
+            .get(endpoint_id)
+            .copied()
+            .unwrap_or(self.threshold)
+    }
+
+    /// The endpoint answered for the sample, mirroring the search: candidates
+    /// below their threshold are dropped, then the best one is discarded when
+    /// the runner-up is closer than the margin.
+    fn predict<'a>(&self, sample: &'a EvaluationSample) -> Option<&'a str> {
+        let mut kept = sample
+            .candidates
+            .iter()
+            .filter(|(id, similarity)| *similarity >= self.threshold_for(id));
+        let (best, best_similarity) = kept.next()?;
+        match kept.next() {
+            Some((_, runner_up)) if best_similarity - runner_up < self.ambiguity_margin => None,
+            _ => Some(best.as_str()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OperatingPoint {
+    // Answered queries whose endpoint is the expected one
+    pub precision: f64,
+    // In-scope queries answered with the expected endpoint
+    pub coverage: f64,
+    // Out-of-scope queries left unanswered
+    pub out_of_scope_rejection: f64,
+    pub answered: usize,
+}
+
+impl OperatingPoint {
+    pub fn measure(samples: &[EvaluationSample], thresholds: &Thresholds) -> Self {
+        let (mut answered, mut correct, mut in_scope, mut out_of_scope, mut rejected) =
+            (0, 0, 0, 0, 0);
+        for sample in samples {
+            let predicted = thresholds.predict(sample);
+            match &sample.expected {
+                Some(_) => in_scope += 1,
+                None => {
+                    out_of_scope += 1;
+                    rejected += predicted.is_none() as usize;
+                }
+            }
+            if let Some(predicted) = predicted {
+                answered += 1;
+                correct += (sample.expected.as_deref() == Some(predicted)) as usize;
+            }
+        }
+        Self {
+            precision: ratio(correct, answered),
+            coverage: ratio(correct, in_scope),
+            out_of_scope_rejection: ratio(rejected, out_of_scope),
+            answered,
+        }
+    }
+
+    fn meets(&self, target: TuningTarget) -> bool {
+        match target {
+            TuningTarget::Precision(precision) => self.precision >= precision,
+            TuningTarget::Coverage(coverage) => self.coverage >= coverage,
+        }
+    }
+
+    // Strictly better for the target, so that ties keep the lowest values
+    fn better_than(&self, other: &Self, target: TuningTarget) -> bool {
+        if !self.meets(target) {
+            return false;
+        }
+        if !other.meets(target) {
+            return true;
+        }
+        let key = |point: &Self| match target {
+            TuningTarget::Precision(_) => (point.coverage, point.precision),
+            TuningTarget::Coverage(_) => (point.precision, point.coverage),
+        };
+        key(self) > key(other)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TuningReport {
+    pub target: TuningTarget,
+    // Scores, and so thresholds, are only comparable under the same settings
+    pub scoring: ScoringSettings,
+    pub current: Thresholds,
+    pub current_point: OperatingPoint,
+    // None when no setting reaches the target
+    pub recommended: Option<Thresholds>,
+    pub recommended_point: Option<OperatingPoint>,
+}
+
+/// Matches the labelled queries with every threshold disabled and tunes
+/// them on the candidates, from the values currently configured.
+pub async fn run_tuning(
+    db: Arc<VectorDB>,
+    config: Arc<Config>,
+    queries: &[LabelledQuery],
+    target: TuningTarget,
+    step: f64,
+) -> AnyhowResult<TuningReport> {
+    if !(step > 0.0 && step <= 1.0) {
+        return Err(anyhow!("The sweep step must be in (0, 1], got {}", step));
+    }
+    let untuned = Arc::new(config.without_thresholds());
+    let samples = collect_samples(db, untuned, queries).await?;
+    Ok(tune_thresholds(
+        &samples,
+        Thresholds::from_config(&config),
+        ScoringSettings::resolve(&config, &SearchOptions::default()),
+        target,
+        step,
+    ))
+}
+
+/// Sweeps the global threshold and the ambiguity margin by `step`, then
+/// tries a threshold of its own for every endpoint, one at a time, keeping
+/// the ones that improve on the target.
+pub fn tune_thresholds(
+    samples: &[EvaluationSample],
+    current: Thresholds,
+    scoring: ScoringSettings,
+    target: TuningTarget,
+    step: f64,
+) -> TuningReport {
+    let current_point = OperatingPoint::measure(samples, &current);
+    let grid = |max: f64| {
+        let steps = (max / step).round() as usize;
+        (0..=steps).map(move |i| (i as f64 * step) as f32)
+    };
+
+    let mut best: Option<(Thresholds, OperatingPoint)> = None;
+    for ambiguity_margin in grid(MAX_MARGIN) {
+        for threshold in grid(1.0) {
+            let thresholds = Thresholds {
+                threshold,
+                ambiguity_margin,
+                endpoints: BTreeMap::new(),
+            };
+            let point = OperatingPoint::measure(samples, &thresholds);
+            if best.as_ref().map_or(point.meets(target), |(_, best)| {
+                point.better_than(best, target)
+            }) {
+                best = Some((thresholds, point));
+            }
+        }
+    }
+
+    if let Some((thresholds, point)) = &mut best {
+        let mut endpoint_ids: Vec<&str> = samples
+            .iter()
+            .flat_map(|s| s.candidates.iter().map(|(id, _)| id.as_str()))
+            .collect();
+        endpoint_ids.sort();
+        endpoint_ids.dedup();
+
+        for endpoint_id in endpoint_ids {
+            for threshold in grid(1.0) {
+                let mut candidate = thresholds.clone();
+                candidate
+                    .endpoints
+                    .insert(endpoint_id.to_string(), threshold);
+                let candidate_point = OperatingPoint::measure(samples, &candidate);
+                if candidate_point.better_than(point, target) {
+                    *thresholds = candidate;
+                    *point = candidate_point;
+                }
+            }
+        }
+    }
+
+    let (recommended, recommended_point) = best.unzip();
+    TuningReport {
+        target,
+        scoring,
+        current,
+        current_point,
+        recommended,
+        recommended_point,
+    }
+}
+
+impl TuningReport {
+    pub fn print(&self) {
+        let describe = |thresholds: &Thresholds, point: &OperatingPoint| {
+            println!(
+                "  threshold {:.2}, ambiguity margin {:.2}: precision {:.1}%, coverage {:.1}%, out-of-scope rejected {:.1}%, {} answered",
+                thresholds.threshold,
+                thresholds.ambiguity_margin,
+                point.precision * 100.0,
+                point.coverage * 100.0,
+                point.out_of_scope_rejection * 100.0,
+                point.answered
+            );
+            for (endpoint_id, threshold) in &thresholds.endpoints {
+                println!("    {}: threshold {:.2}", endpoint_id, threshold);
+            }
+        };
+
+        println!(
+            "Scores from {:?} search, {:?} fusion, re-ranking {}, {:?} aggregation",
+            self.scoring.mode, self.scoring.fusion, self.scoring.rerank, self.scoring.aggregation
+        );
+        println!(
+            "Figures are in-sample: measured on the queries the values were tuned on, so expect lower results on new queries. Check them on a held-out file with `matcher eval`."
+        );
+        println!("Current settings:");
+        describe(&self.current, &self.current_point);
+        match (&self.recommended, &self.recommended_point) {
+            (Some(thresholds), Some(point)) => {
+                println!("Recommended for {:?}:", self.target);
+                describe(thresholds, point);
+            }
+            _ => println!(
+                "No threshold or margin reaches {:?} on this dataset",
+                self.target
+            ),
+        }
+    }
+}
+
+/// Tuned thresholds saved next to the endpoints file, which is left
+/// untouched, and applied over it by `Config::load_from_yaml`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThresholdOverrides {
+    // Ignored when the search no longer scores the same way
+    pub scoring: ScoringSettings,
+    #[serde(flatten)]
+    pub thresholds: Thresholds,
+}
+
+impl ThresholdOverrides {
+    /// `endpoints.thresholds.yaml` for `endpoints.yaml`.
+    pub fn path(config_path: &Path) -> PathBuf {
+        let stem = config_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        config_path.with_file_name(format!("{}.thresholds.yaml", stem))
+    }
+
+    /// Replaces the thresholds of `config` with the tuned ones, when the
+    /// overrides file exists and was tuned under the same scoring settings.
+    /// Endpoints without a tuned threshold use the global one.
+    pub fn apply(config: &mut Config, config_path: &Path) -> AnyhowResult<()> {
+        let path = Self::path(config_path);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+        };
+        let overrides: Self =
+            serde_yaml::from_str(&content).with_context(|| format!("Invalid {:?}", path))?;
+
+        let scoring = ScoringSettings::resolve(config, &SearchOptions::default());
+        if overrides.scoring != scoring {
+            warn!(
+                "Ignoring {:?}: tuned for {:?}, the search now uses {:?}; run `matcher tune` again",
+                path, overrides.scoring, scoring
+            );
+            return Ok(());
+        }
+
+        let thresholds = overrides.thresholds;
+        config.search.threshold = thresholds.threshold;
+        config.search.ambiguity_margin = thresholds.ambiguity_margin;
+        for endpoint in &mut config.endpoints {
+            endpoint.threshold = thresholds.endpoints.get(&endpoint.id).copied();
+        }
+        Ok(())
+    }
+
+    /// Writes the recommendation of `report` next to the endpoints file and
+    /// returns where.
+    pub fn write(config_path: &Path, report: &TuningReport) -> AnyhowResult<PathBuf> {
+        let thresholds = report
+            .recommended
+            .as_ref()
+            .ok_or_else(|| anyhow!("No recommended thresholds to write"))?;
+        let overrides = Self {
+            scoring: report.scoring,
+            thresholds: Thresholds {
+                threshold: rounded(thresholds.threshold),
+                ambiguity_margin: rounded(thresholds.ambiguity_margin),
+                endpoints: thresholds
+                    .endpoints
+                    .iter()
+                    .map(|(id, threshold)| (id.clone(), rounded(*threshold)))
+                    .collect(),
+            },
+        };
+
+        let path = Self::path(config_path);
+        let content = format!(
+            "# Written by `matcher tune --write`, overrides the thresholds of {}\n{}",
+            config_path.display(),
+            serde_yaml::to_string(&overrides)?
+        );
+        std::fs::write(&path, content).with_context(|| format!("Failed to write {:?}", path))?;
+        Ok(path)
+    }
+}
+
+// Grid values are multiples of the step, keep them readable in the file
+fn rounded(value: f32) -> f32 {
+    ((value as f64 * 1000.0).round() / 1000.0) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluation::metrics::test_sample as sample;
+
+    #[test]
+    fn test_recommends_thresholds_reaching_the_target() {
+        let samples = vec![
+            sample(
+                Some("send_email"),
+                &[("send_email", 0.9), ("start_app", 0.4)],
+            ),
+            sample(
+                Some("send_email"),
+                &[("send_email", 0.8), ("start_app", 0.5)],
+            ),
+            sample(
+                Some("start_app"),
+                &[("start_app", 0.85), ("send_email", 0.3)],
+            ),
+            // Ambiguous in-scope query, answered wrongly
+            sample(
+                Some("start_app"),
+                &[("send_email", 0.86), ("start_app", 0.85)],
+            ),
+            // Out of scope, but close to start_app
+            sample(None, &[("start_app", 0.6), ("send_email", 0.2)]),
+        ];
+        let current = Thresholds {
+            threshold: 0.0,
+            ambiguity_margin: 0.0,
+            endpoints: BTreeMap::new(),
+        };
+
+        let scoring = ScoringSettings::resolve(&Config::default(), &SearchOptions::default());
+        let report = tune_thresholds(
+            &samples,
+            current,
+            scoring,
+            TuningTarget::Precision(1.0),
+            0.01,
+        );
+        assert!((report.current_point.precision - 0.6).abs() < 1e-9);
+
+        let recommended = report.recommended.unwrap();
+        let point = report.recommended_point.unwrap();
+        assert!((point.precision - 1.0).abs() < 1e-9);
+        assert!((point.coverage - 0.75).abs() < 1e-9);
+        assert!((point.out_of_scope_rejection - 1.0).abs() < 1e-9);
+        // Only the margin tells the ambiguous query apart from the right ones
+        assert!(recommended.ambiguity_margin > 0.0 && recommended.ambiguity_margin <= 0.05);
+        assert!(recommended.threshold > 0.6 && recommended.threshold <= 0.8);
+
+        let unreachable = tune_thresholds(
+            &[sample(None, &[("start_app", 0.6)])],
+            Thresholds::from_config(&Config::default()),
+            scoring,
+            TuningTarget::Coverage(0.5),
+            0.1,
+        );
+        assert!(unreachable.recommended.is_none());
+    }
+
+    #[test]
+    fn test_write_threshold_overrides() {
+        let directory = std::env::temp_dir().join(format!("matcher-tune-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("endpoints.yaml");
+        let endpoints = r#"
+# Kept as written
+search:
+  threshold: 0.0
+endpoints:
+  - id: "send_email"
+    text: "envoyer un email"
+    description: "Envoyer un email"
+    patterns: ["envoyer un mail à {email}"]
+    threshold: 0.3
+  - id: "start_app"
+    text: "démarrer l'application"
+    description: "Démarrer l'application"
+    patterns: ["démarre l'application"]
+"#;
+        std::fs::write(&path, endpoints).unwrap();
+
+        let config = Config::load_from_yaml(&path).unwrap();
+        let report = TuningReport {
+            target: TuningTarget::Precision(0.95),
+            scoring: ScoringSettings::resolve(&config, &SearchOptions::default()),
+            current: Thresholds::from_config(&config),
+            current_point: OperatingPoint::measure(&[], &Thresholds::from_config(&config)),
+            recommended: Some(Thresholds {
+                threshold: 0.42,
+                ambiguity_margin: 0.03,
+                endpoints: BTreeMap::from([("start_app".to_string(), 0.61)]),
+            }),
+            recommended_point: None,
+        };
+        let written = ThresholdOverrides::write(&path, &report).unwrap();
+        assert_eq!(written, directory.join("endpoints.thresholds.yaml"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), endpoints);
+
+        let config = Config::load_from_yaml(&path).unwrap();
+        assert_eq!(config.search.threshold, 0.42);
+        assert_eq!(config.search.ambiguity_margin, 0.03);
+        assert_eq!(config.threshold_for("send_email"), 0.42);
+        assert_eq!(config.threshold_for("start_app"), 0.61);
+
+        // Tuned under other scoring settings, the overrides are ignored
+        let mut stale = report;
+        stale.scoring.rerank = !stale.scoring.rerank;
+        ThresholdOverrides::write(&path, &stale).unwrap();
+        let config = Config::load_from_yaml(&path).unwrap();
+        assert_eq!(config.search.threshold, 0.0);
+        assert_eq!(config.threshold_for("send_email"), 0.3);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
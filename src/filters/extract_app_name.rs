@@ -2,9 +2,16 @@ use crate::preprocessing::APP_PATTERNS;
 
 pub fn extract_app_name(text: &str) -> Option<String> {
     let text = text.to_lowercase();
+    // Text already covered by a longer prefix: "de " inside "de l'application "
+    // must not pick up "l'application" as the app name
+    let mut claimed: Vec<(usize, usize)> = Vec::new();
 
     for (prefix, suffix) in APP_PATTERNS.iter() {
-        if let Some(start_pos) = text.find(prefix) {
+        let found = text.match_indices(prefix).map(|(pos, _)| pos).find(|pos| {
+            !claimed.iter().any(|(start, end)| pos >= start && pos < end)
+        });
+        if let Some(start_pos) = found {
+            claimed.push((start_pos, start_pos + prefix.len()));
             let start = start_pos + prefix.len();
             let remaining = &text[start..];
 
@@ -1,108 +1,237 @@
-use crate::config::Config;
+use crate::actions::{dispatch_action, uses_iggy, Action, DispatchOutcome};
+use crate::config::ConversationContext;
+use crate::config::{AggregationStrategy, Config, FusionMethod, SearchMode, SearchOptions};
+use crate::conversation::context_store::ContextStore;
+use crate::conversation::resolve_follow_up::resolve_follow_up;
 use crate::database::vector_db::VectorDB;
+use crate::interaction::endpoint::create_endpoint_match;
 use crate::interaction::handlers::{
-    handle_confirmation, handle_initial_query, handle_parameter_value,
+    handle_back, handle_cancel, handle_confirmation, handle_initial_query, handle_parameter_value,
+    handle_skip_parameter, resend_pending_prompt, send_invalid_command,
 };
+use crate::interaction::session_store::{create_session_store, Session, SessionStore};
 use crate::interaction::state::InteractionState;
-use crate::preprocessing::preprocess_query::preprocess_query;
+use crate::preprocessing::split_intents::{action_words, split_intents};
+use anyhow::Result as AnyhowResult;
 use futures::StreamExt;
-use matcher::MatchResponse;
 use matcher::{
     interactive_request::Request as InteractiveRequestType, EndpointMatch, InteractiveRequest,
     InteractiveResponse,
 };
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio_stream::Stream;
 use tonic::{Request, Response, Status, Streaming};
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 pub mod matcher {
     tonic::include_proto!("matcher");
 }
 
+// Candidates returned when a request asks for all matches
+pub const ALL_MATCHES_LIMIT: usize = 5;
+
 use crate::grpc::matcher_service::matcher::interactive_response::Response as InteractiveResponseType;
 pub struct MatcherService {
     #[allow(dead_code)]
     pub config: Arc<Config>,
     pub db: Arc<VectorDB>,
+    pub contexts: ContextStore,
+    pub sessions: Arc<dyn SessionStore>,
+}
+
+impl MatcherService {
+    pub fn new(config: Arc<Config>, db: Arc<VectorDB>) -> Self {
+        Self {
+            contexts: ContextStore::new(Duration::from_secs(config.context.ttl_secs)),
+            sessions: create_session_store(&config.sessions),
+            config,
+            db,
+        }
+    }
 }
 
 #[tonic::async_trait]
 impl matcher::matcher_server::Matcher for MatcherService {
+    async fn health(
+        &self,
+        _request: Request<matcher::HealthRequest>,
+    ) -> Result<Response<matcher::HealthResponse>, Status> {
+        let iggy_required = uses_iggy(&self.config);
+        let iggy = self.config.iggy_pool().health();
+        let degraded = iggy_required && !iggy.connected;
+        Ok(Response::new(matcher::HealthResponse {
+            status: if degraded { "degraded" } else { "ok" }.to_string(),
+            iggy_required,
+            iggy_connected: iggy.connected,
+            iggy_error: iggy.last_error.unwrap_or_default(),
+        }))
+    }
+
     async fn match_query(
         &self,
         request: Request<matcher::MatchRequest>,
     ) -> Result<Response<matcher::MatchResponse>, Status> {
         let req = request.into_inner();
-        let processed = preprocess_query(&req.query, &req.language);
 
         info!(
             "Received match request - query: {}, language: {}, show_all_matches: {}",
             req.query, req.language, req.show_all_matches
         );
 
-        let (results, best_similarity) = match self
-            .db
-            .search_similar(
-                &processed.cleaned_text,
+        let clauses = if req
+            .split_intents
+            .unwrap_or(self.config.preprocessing.split_intents)
+        {
+            split_intents(
+                &req.query,
                 &req.language,
-                if req.show_all_matches { 5 } else { 1 },
-                &self.config,
+                &action_words(&self.config, &req.language),
             )
-            .await
-        {
-            Ok((results, similarity)) => {
-                if results.is_empty() {
-                    warn!("No matches found for query: {}", req.query);
+        } else {
+            vec![req.query.clone()]
+        };
+        if clauses.len() > 1 {
+            info!("Split query into {} intents: {:?}", clauses.len(), clauses);
+        }
+
+        let session_id = (self.config.context.enabled && !req.session_id.is_empty())
+            .then_some(req.session_id.as_str());
+        let mut context = session_id.and_then(|id| self.contexts.get(id));
+
+        let options = search_options(&req);
+        let limit = if req.show_all_matches {
+            ALL_MATCHES_LIMIT
+        } else {
+            1
+        };
+        let mut clause_results = Vec::with_capacity(clauses.len());
+        for clause in clauses {
+            // The search preprocesses the clause, spell correction included
+            let mut outcome = match self
+                .db
+                .search_similar_with_options(&clause, &req.language, limit, &self.config, &options)
+                .await
+            {
+                Ok(outcome) => {
+                    if outcome.results.is_empty() {
+                        warn!("No matches found for query: {}", clause);
+                    }
+                    outcome
                 }
-                (results, similarity)
+                Err(e) => {
+                    error!("Search failed: {}", e);
+                    return Err(Status::internal(format!("Search failed: {}", e)));
+                }
+            };
+
+            let processed = outcome.processed.clone();
+            let follow_up = context.as_ref().is_some_and(|context| {
+                resolve_follow_up(&clause, &req.language, &mut outcome, context, &self.config)
+            });
+            if follow_up {
+                info!("'{}' continues the previous request of the session", clause);
             }
-            Err(e) => {
-                error!("Search failed: {}", e);
-                return Err(Status::internal(format!("Search failed: {}", e)));
+            if let Some(best) = outcome.results.first() {
+                context = Some(ConversationContext {
+                    endpoint_id: best.endpoint_id.clone(),
+                    parameters: best.parameters.clone(),
+                    similarity: best.similarity,
+                    confidence: best.confidence,
+                });
             }
-        };
+            clause_results.push((clause, processed, outcome, follow_up));
+        }
+        if let (Some(session_id), Some(context)) = (session_id, context) {
+            self.contexts.set(session_id, context);
+        }
 
-        let matches: Vec<matcher::EndpointMatch> = results
+        let intents: Vec<matcher::Intent> = clause_results
             .iter()
-            .map(|result| {
-                EndpointMatch {
-                    endpoint_id: result.endpoint_id.clone(),
-                    similarity: result.similarity as f64, // Remove the 1.0 - conversion
-                    parameters: result.parameters.clone(),
-                    is_negated: processed.is_negated,
-                    missing_required: result
-                        .parameter_analysis
-                        .missing_required
-                        .iter()
-                        .map(|p| matcher::ParameterInfo {
-                            name: p.name.clone(),
-                            description: p.description.clone(),
-                            required: true,
-                        })
-                        .collect(),
-                    missing_optional: result
-                        .parameter_analysis
-                        .missing_optional
-                        .iter()
-                        .map(|p| matcher::ParameterInfo {
-                            name: p.name.clone(),
-                            description: p.description.clone(),
-                            required: false,
-                        })
-                        .collect(),
-                }
+            .map(|(clause, processed, outcome, follow_up)| matcher::Intent {
+                clause: clause.clone(),
+                endpoint_match: outcome.results.first().map(|result| {
+                    create_endpoint_match(result, processed.is_negated, result.similarity)
+                }),
+                follow_up: *follow_up,
             })
             .collect();
 
-        let score = best_similarity as f64; // This should match the similarities now
+        // A single clause keeps every candidate, a compound query the best match of each intent
+        let matches: Vec<EndpointMatch> = match clause_results.as_slice() {
+            [(_, processed, outcome, _)] => outcome
+                .results
+                .iter()
+                .map(|result| {
+                    create_endpoint_match(result, processed.is_negated, result.similarity)
+                })
+                .collect(),
+            _ => intents
+                .iter()
+                .filter_map(|intent| intent.endpoint_match.clone())
+                .collect(),
+        };
+
+        let score = clause_results
+            .iter()
+            .map(|(_, _, outcome, _)| outcome.best_similarity as f64)
+            .fold(0.0, f64::max);
         let has_matches = !matches.is_empty();
+        let debug_info = req.debug.then(|| {
+            let corrected = clause_results
+                .iter()
+                .any(|(_, processed, _, _)| processed.corrected_text.is_some());
+            matcher::DebugInfo {
+                original_query: req.query.clone(),
+                corrected_query: if corrected {
+                    clause_results
+                        .iter()
+                        .map(|(clause, processed, _, _)| {
+                            processed
+                                .corrected_text
+                                .clone()
+                                .unwrap_or_else(|| clause.clone())
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" | ")
+                } else {
+                    String::new()
+                },
+                processed_query: clause_results
+                    .iter()
+                    .map(|(_, processed, _, _)| processed.cleaned_text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" | "),
+                corrections: clause_results
+                    .iter()
+                    .flat_map(|(_, processed, _, _)| &processed.corrections)
+                    .map(|c| matcher::SpellCorrection {
+                        original: c.original.clone(),
+                        corrected: c.corrected.clone(),
+                        distance: c.distance as u32,
+                    })
+                    .collect(),
+                suppressed: clause_results
+                    .iter()
+                    .flat_map(|(_, _, outcome, _)| &outcome.suppressed)
+                    .map(|s| matcher::SuppressedMatch {
+                        endpoint_id: s.endpoint_id.clone(),
+                        negative_pattern: s.negative_pattern.clone(),
+                        similarity: s.similarity as f64,
+                        excluded: s.excluded,
+                        reason: s.reason.clone(),
+                    })
+                    .collect(),
+            }
+        });
 
         Ok(Response::new(matcher::MatchResponse {
             matches,
             score,
             has_matches,
+            debug_info,
+            intents,
         }))
     }
 
@@ -117,91 +246,199 @@ impl matcher::matcher_server::Matcher for MatcherService {
         let (tx, rx) = mpsc::channel(128);
         let db = self.db.clone();
         let config = self.config.clone();
+        let sessions = self.sessions.clone();
 
         tokio::spawn(async move {
             let mut state: Option<InteractionState> = None;
+            let mut session: Option<Session> = None;
 
-            while let Some(req) = in_stream.next().await {
-                match req {
-                    Ok(interactive_req) => match interactive_req.request {
-                        Some(InteractiveRequestType::InitialQuery(initial_query)) => {
-                            state = handle_initial_query(
-                                &initial_query.query,
-                                &initial_query.language,
-                                &db,
-                                &config,
-                                &tx,
-                            )
-                            .await;
-                            println!("State after initial query: {:?}", state); // Debug log
+            loop {
+                let next = match step_timeout(&config, &state) {
+                    Some(timeout) => match tokio::time::timeout(timeout, in_stream.next()).await {
+                        Ok(next) => next,
+                        Err(_) => {
+                            if let Some(timed_out) = state.take() {
+                                warn!(
+                                    "No answer in {:?} while {}, abandoning session",
+                                    timeout,
+                                    timed_out.name()
+                                );
+                                let _ = tx
+                                    .send(Ok(InteractiveResponse {
+                                        response: Some(InteractiveResponseType::SessionTimedOut(
+                                            matcher::SessionTimedOut {
+                                                session_id: session
+                                                    .as_ref()
+                                                    .map(|s| s.id.clone())
+                                                    .unwrap_or_default(),
+                                                state: timed_out.name().to_string(),
+                                                timeout_secs: timeout.as_secs() as u32,
+                                            },
+                                        )),
+                                    }))
+                                    .await;
+                            }
+                            sync_session(&sessions, &mut session, &state).await;
+                            continue;
                         }
-                        Some(InteractiveRequestType::ConfirmationResponse(confirmation)) => {
-                            if let Some(current_state) = state {
-                                state =
-                                    handle_confirmation(confirmation.confirmed, current_state, &tx)
+                    },
+                    None => in_stream.next().await,
+                };
+                let Some(req) = next else {
+                    break;
+                };
+
+                match req {
+                    Ok(interactive_req) => {
+                        match interactive_req.request {
+                            Some(InteractiveRequestType::Restart(restart))
+                                if restart.query.trim().is_empty() =>
+                            {
+                                send_invalid_command(
+                                    "restart",
+                                    "restart needs a new query",
+                                    state.as_ref(),
+                                    &tx,
+                                )
+                                .await;
+                            }
+                            Some(InteractiveRequestType::InitialQuery(matcher::InitialQuery {
+                                query,
+                                language,
+                            }))
+                            | Some(InteractiveRequestType::Restart(matcher::Restart {
+                                query,
+                                language,
+                            })) => {
+                                state = handle_initial_query(&query, &language, &db, &config, &tx)
+                                    .await;
+                                debug!("State after initial query: {:?}", state);
+
+                                // A new query replaces whatever this stream was doing
+                                if let Some(previous) = session.take() {
+                                    if let Err(e) = sessions.remove(&previous.id).await {
+                                        error!("Failed to remove session {}: {}", previous.id, e);
+                                    }
+                                }
+                                if let Some(current_state) = &state {
+                                    let new_session =
+                                        Session::new(&query, &language, current_state.clone());
+                                    info!("Started session {}", new_session.id);
+                                    let _ = tx
+                                        .send(Ok(InteractiveResponse {
+                                            response: Some(
+                                                InteractiveResponseType::SessionStarted(
+                                                    matcher::SessionStarted {
+                                                        session_id: new_session.id.clone(),
+                                                    },
+                                                ),
+                                            ),
+                                        }))
                                         .await;
-                                println!("State after confirmation: {:?}", state);
-                                // Debug log
+                                    session = Some(new_session);
+                                }
                             }
-                        }
-                        Some(InteractiveRequestType::ParameterValue(param_value)) => {
-                            if let Some(current_state) = state.take() {
-                                state =
-                                    handle_parameter_value(param_value, current_state, &tx).await;
-
-                                // Handle completed state
-                                if let Some(InteractionState::Completed { endpoint_match }) = &state
-                                {
-                                    // Now we're actually using the endpoint_match field
-                                    println!(
-                                        "✅ Executing completed action for endpoint: {}",
-                                        endpoint_match.endpoint_id
-                                    );
-
-                                    match execute_completed_action(endpoint_match).await {
-                                        Ok(_) => {
-                                            let response = MatchResponse {
-                                                matches: vec![endpoint_match.clone()],
-                                                score: 1.0,
-                                                has_matches: true,
-                                            };
-
-                                            if let Err(e) = tx
-                                                .send(Ok(InteractiveResponse {
-                                                    response: Some(
-                                                        InteractiveResponseType::MatchResult(
-                                                            response,
-                                                        ),
+                            Some(InteractiveRequestType::ResumeSession(resume)) => {
+                                match sessions.load(&resume.session_id).await {
+                                    Ok(Some(resumed)) => {
+                                        info!("Resumed session {}", resumed.id);
+                                        let _ = tx
+                                            .send(Ok(InteractiveResponse {
+                                                response: Some(
+                                                    InteractiveResponseType::SessionResumed(
+                                                        matcher::SessionResumed {
+                                                            session_id: resumed.id.clone(),
+                                                            state: resumed.state.name().to_string(),
+                                                        },
                                                     ),
-                                                }))
-                                                .await
-                                            {
-                                                error!("Failed to send completion response: {}", e);
-                                            }
-                                        }
-                                        Err(e) => {
-                                            error!("Failed to execute completed action: {}", e);
-                                            if let Err(send_err) = tx
-                                                .send(Err(Status::internal(format!(
-                                                    "Action execution failed: {}",
-                                                    e
-                                                ))))
-                                                .await
-                                            {
-                                                error!(
-                                                    "Failed to send error response: {}",
-                                                    send_err
-                                                );
-                                            }
+                                                ),
+                                            }))
+                                            .await;
+                                        if let Err(e) =
+                                            resend_pending_prompt(&resumed.state, &tx).await
+                                        {
+                                            error!("Failed to resend pending prompt: {}", e);
                                         }
+                                        state = Some(resumed.state.clone());
+                                        session = Some(resumed);
+                                    }
+                                    Ok(None) => {
+                                        let _ = tx
+                                            .send(Err(Status::not_found(format!(
+                                                "Session {} not found or expired",
+                                                resume.session_id
+                                            ))))
+                                            .await;
+                                    }
+                                    Err(e) => {
+                                        error!(
+                                            "Failed to load session {}: {}",
+                                            resume.session_id, e
+                                        );
+                                        let _ = tx
+                                            .send(Err(Status::internal("Failed to load session")))
+                                            .await;
                                     }
                                 }
                             }
+                            Some(InteractiveRequestType::ConfirmationResponse(confirmation)) => {
+                                if let Some(current_state) = state {
+                                    state = handle_confirmation(
+                                        confirmation.confirmed,
+                                        current_state,
+                                        &config,
+                                        &tx,
+                                    )
+                                    .await;
+                                    debug!("State after confirmation: {:?}", state);
+                                    // Debug log
+                                } else {
+                                    send_invalid_command(
+                                        "confirmation_response",
+                                        "no confirmation is pending",
+                                        None,
+                                        &tx,
+                                    )
+                                    .await;
+                                }
+                            }
+                            Some(InteractiveRequestType::Cancel(_)) => {
+                                state = handle_cancel(state.take(), &tx).await;
+                            }
+                            Some(InteractiveRequestType::SkipParameter(skip)) => {
+                                state =
+                                    handle_skip_parameter(skip, state.take(), &config, &tx).await;
+                            }
+                            Some(InteractiveRequestType::Back(_)) => {
+                                state = handle_back(state.take(), &tx).await;
+                                debug!("State after back: {:?}", state);
+                            }
+                            Some(InteractiveRequestType::ParameterValue(param_value)) => {
+                                if let Some(current_state) = state.take() {
+                                    state = handle_parameter_value(
+                                        param_value,
+                                        current_state,
+                                        &config,
+                                        &tx,
+                                    )
+                                    .await;
+                                } else {
+                                    send_invalid_command(
+                                        "parameter_value",
+                                        "no parameter is being collected",
+                                        None,
+                                        &tx,
+                                    )
+                                    .await;
+                                }
+                            }
+                            None => {
+                                error!("Received empty request");
+                            }
                         }
-                        None => {
-                            error!("Received empty request");
-                        }
-                    },
+                        dispatch_completed(&mut state, session.as_ref(), &config, &tx).await;
+                        sync_session(&sessions, &mut session, &state).await;
+                    }
                     Err(e) => {
                         error!("Error receiving request: {}", e);
                         let _ = tx.send(Err(Status::internal("Stream error"))).await;
@@ -217,45 +454,235 @@ impl matcher::matcher_server::Matcher for MatcherService {
     }
 }
 
-//use crate::grpc::matcher_service::matcher::EndpointMatch;
-use crate::messaging::get_authenticated_iggy_client::get_authenticated_iggy_client;
-use crate::messaging::send_structured_message::send_structured_message;
-use anyhow::{anyhow, Result as AnyhowResult};
-//use tracing::{error, info};
+// Dispatches the action of an interaction that just completed and clears the
+// state, so that a message arriving after completion cannot dispatch it again
+async fn dispatch_completed(
+    state: &mut Option<InteractionState>,
+    session: Option<&Session>,
+    config: &Config,
+    tx: &mpsc::Sender<Result<InteractiveResponse, Status>>,
+) {
+    let endpoint_match = match state.take() {
+        Some(InteractionState::Completed { endpoint_match }) => endpoint_match,
+        other => {
+            *state = other;
+            return;
+        }
+    };
+    println!(
+        "✅ Executing completed action for endpoint: {}",
+        endpoint_match.endpoint_id
+    );
+
+    let outcome = match execute_completed_action(&endpoint_match, session, config).await {
+        Ok(DispatchOutcome::Dispatched {
+            endpoint_id: dispatched_endpoint_id,
+            negated,
+        }) => InteractiveResponseType::ActionExecuted(matcher::ActionExecuted {
+            endpoint_id: endpoint_match.endpoint_id.clone(),
+            message: format!("Action for '{}' dispatched", dispatched_endpoint_id),
+            dispatched_endpoint_id,
+            negated,
+        }),
+        Ok(DispatchOutcome::Duplicate { endpoint_id }) => {
+            InteractiveResponseType::ActionExecuted(matcher::ActionExecuted {
+                endpoint_id: endpoint_match.endpoint_id.clone(),
+                message: format!("Action for '{}' already dispatched", endpoint_id),
+                dispatched_endpoint_id: endpoint_id,
+                negated: endpoint_match.is_negated,
+            })
+        }
+        Ok(DispatchOutcome::Queued { error, .. }) => {
+            InteractiveResponseType::ActionFailed(matcher::ActionFailed {
+                endpoint_id: endpoint_match.endpoint_id.clone(),
+                reason: error,
+                will_retry: true,
+            })
+        }
+        Ok(DispatchOutcome::Suppressed) => {
+            InteractiveResponseType::ActionSuppressed(matcher::ActionSuppressed {
+                endpoint_id: endpoint_match.endpoint_id.clone(),
+                reason: "negated query".to_string(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to execute completed action: {}", e);
+            InteractiveResponseType::ActionFailed(matcher::ActionFailed {
+                endpoint_id: endpoint_match.endpoint_id.clone(),
+                reason: e.to_string(),
+                will_retry: false,
+            })
+        }
+    };
+    if let Err(e) = tx
+        .send(Ok(InteractiveResponse {
+            response: Some(outcome),
+        }))
+        .await
+    {
+        error!("Failed to send action outcome: {}", e);
+    }
+}
 
-async fn execute_completed_action(endpoint_match: &EndpointMatch) -> AnyhowResult<()> {
+// How long the client has to answer the prompt `state` is waiting on
+fn step_timeout(config: &Config, state: &Option<InteractionState>) -> Option<Duration> {
+    let secs = match state {
+        Some(InteractionState::AwaitingConfirmation { .. }) => {
+            config.sessions.confirmation_timeout_secs
+        }
+        Some(InteractionState::CollectingParameters { .. }) => {
+            config.sessions.parameter_timeout_secs
+        }
+        _ => 0,
+    };
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+// Saves the session after each request, or drops it once there is nothing
+// left to resume (completed, cancelled or failed)
+async fn sync_session(
+    sessions: &Arc<dyn SessionStore>,
+    session: &mut Option<Session>,
+    state: &Option<InteractionState>,
+) {
+    let result = match (session.as_mut(), state) {
+        (None, _) => return,
+        (Some(current), Some(state)) => {
+            current.state = state.clone();
+            current.touch();
+            sessions.save(current).await
+        }
+        (Some(current), None) => {
+            let id = current.id.clone();
+            *session = None;
+            sessions.remove(&id).await
+        }
+    };
+    if let Err(e) = result {
+        error!("Failed to update session: {}", e);
+    }
+}
+
+fn search_options(req: &matcher::MatchRequest) -> SearchOptions {
+    SearchOptions {
+        mode: match req.search_mode() {
+            matcher::SearchMode::Unspecified => None,
+            matcher::SearchMode::Vector => Some(SearchMode::Vector),
+            matcher::SearchMode::Hybrid => Some(SearchMode::Hybrid),
+        },
+        fusion: match req.fusion() {
+            matcher::FusionMethod::Unspecified => None,
+            matcher::FusionMethod::WeightedSum => Some(FusionMethod::WeightedSum),
+            matcher::FusionMethod::ReciprocalRank => Some(FusionMethod::ReciprocalRank),
+        },
+        rerank: req.rerank,
+        aggregation: match req.aggregation() {
+            matcher::AggregationStrategy::Unspecified => None,
+            matcher::AggregationStrategy::Max => Some(AggregationStrategy::Max),
+            matcher::AggregationStrategy::MeanTopK => Some(AggregationStrategy::MeanTopK),
+            matcher::AggregationStrategy::Softmax => Some(AggregationStrategy::Softmax),
+        },
+    }
+}
+
+async fn execute_completed_action(
+    endpoint_match: &EndpointMatch,
+    session: Option<&Session>,
+    config: &Config,
+) -> AnyhowResult<DispatchOutcome> {
     info!(
         "Processing completed match for endpoint: {}",
         endpoint_match.endpoint_id
     );
 
-    let client = get_authenticated_iggy_client().await?;
-
-    // Convert parameters map to vector of values
-    let message_params: Vec<String> = endpoint_match.parameters.values().cloned().collect();
-
-    // Send message to Iggy
-    send_structured_message(
-        &client,
-        "gibro",        // stream name
-        "notification", // topic name
-        &endpoint_match.endpoint_id,
-        message_params,
-    )
-    .await
-    .map_err(|e| {
+    let action = Action {
+        endpoint_id: endpoint_match.endpoint_id.clone(),
+        parameters: endpoint_match.parameters.clone(),
+        similarity: endpoint_match.similarity as f32,
+        negated: endpoint_match.is_negated,
+        query: session.map(|s| s.query.clone()),
+        language: session.map(|s| s.language.clone()),
+        session_id: session.map(|s| s.id.clone()),
+        // A session completes once, its id keeps retries idempotent
+        request_id: session.map(|s| s.id.clone()),
+    };
+    let outcome = dispatch_action(&action, config).await.map_err(|e| {
         error!(
-            "Failed to send message for endpoint {}: {}",
+            "Failed to dispatch action for endpoint {}: {}",
             endpoint_match.endpoint_id, e
         );
-        anyhow!("Message sending failed: {}", e)
+        e
     })?;
 
-    info!(
-        "Sent notification for endpoint: {} with similarity: {}",
-        endpoint_match.endpoint_id, endpoint_match.similarity
-    );
-
     info!("Completed processing match");
-    Ok(())
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_completed_action_dispatched_once() {
+        let path =
+            std::env::temp_dir().join(format!("matcher-completed-{}.jsonl", uuid::Uuid::new_v4()));
+        let config: Config = serde_yaml::from_str(&format!(
+            r#"
+endpoints:
+  - id: "send_email"
+    text: "envoyer un email"
+    description: "Envoyer un email"
+actions:
+  default:
+    type: "file"
+    path: "{}"
+  outbox:
+    enabled: false
+"#,
+            path.display()
+        ))
+        .unwrap();
+        let (tx, mut rx) = mpsc::channel(16);
+        let endpoint_match = EndpointMatch {
+            endpoint_id: "send_email".to_string(),
+            ..Default::default()
+        };
+        let session = Session::new(
+            "envoie un mail",
+            "fr",
+            InteractionState::Completed {
+                endpoint_match: endpoint_match.clone(),
+            },
+        );
+        let mut state = Some(InteractionState::Completed {
+            endpoint_match: endpoint_match.clone(),
+        });
+
+        dispatch_completed(&mut state, Some(&session), &config, &tx).await;
+        assert!(state.is_none());
+        assert!(matches!(
+            rx.try_recv().unwrap().unwrap().response,
+            Some(InteractiveResponseType::ActionExecuted(_))
+        ));
+
+        // A stray parameter value reaching the completed state is rejected
+        // and does not dispatch the action a second time
+        let parameter_value = matcher::ParameterValue {
+            parameter_name: "email".to_string(),
+            value: "toto@gmail.com".to_string(),
+        };
+        let completed = InteractionState::Completed { endpoint_match };
+        state = handle_parameter_value(parameter_value, completed, &config, &tx).await;
+        dispatch_completed(&mut state, Some(&session), &config, &tx).await;
+
+        assert!(state.is_none());
+        assert!(matches!(
+            rx.try_recv().unwrap().unwrap().response,
+            Some(InteractiveResponseType::InvalidCommand(_))
+        ));
+        assert!(rx.try_recv().is_err());
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written.lines().count(), 1);
+        std::fs::remove_file(path).unwrap();
+    }
 }
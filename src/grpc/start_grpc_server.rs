@@ -1,20 +1,24 @@
+use crate::actions::uses_iggy;
 use crate::config::Config;
+use crate::constants::DB_PATH;
 use crate::database::vector_db::VectorDB;
+use crate::interaction::session_store::SessionStore;
 
 use super::matcher_service::matcher::matcher_server::MatcherServer;
 use super::matcher_service::MatcherService;
 
 use std::sync::Arc;
+use std::time::Duration;
 use tonic::transport::Server;
 use tonic_reflection::server::Builder as ReflectionBuilder;
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
 
 pub async fn start_grpc_server(config: Arc<Config>) -> Result<(), Box<dyn std::error::Error>> {
     let addr = "[::]:50030".parse()?;
     info!("Connecting to VectorDB");
 
     // Simply connect to existing database without initialization
-    let db = match VectorDB::new("data/mydb", None, false).await {
+    let db = match VectorDB::new(DB_PATH, None, false).await {
         Ok(db) => Arc::new(db),
         Err(e) => {
             error!("Failed to connect to VectorDB: {}. Make sure to initialize the database first using --reload flag", e);
@@ -22,7 +26,14 @@ pub async fn start_grpc_server(config: Arc<Config>) -> Result<(), Box<dyn std::e
         }
     };
 
-    let matcher_service = MatcherService { config, db };
+    if uses_iggy(&config) {
+        spawn_iggy_monitor(config.clone());
+    }
+    if config.actions.outbox.enabled {
+        spawn_outbox_retry(config.clone());
+    }
+    let matcher_service = MatcherService::new(config, db);
+    spawn_session_purge(matcher_service.sessions.clone());
 
     // Get the file descriptor set
     let descriptor_set = include_bytes!(concat!(env!("OUT_DIR"), "/matcher_descriptor.bin"));
@@ -42,3 +53,69 @@ pub async fn start_grpc_server(config: Arc<Config>) -> Result<(), Box<dyn std::e
     info!("gRPC server has been shut down");
     Ok(())
 }
+
+// Periodically drops idle interactive sessions
+fn spawn_session_purge(sessions: Arc<dyn SessionStore>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            match sessions.purge_expired().await {
+                Ok(0) => {}
+                Ok(purged) => debug!("Purged {} idle sessions", purged),
+                Err(e) => error!("Failed to purge idle sessions: {}", e),
+            }
+        }
+    });
+}
+
+// Connects to Iggy at startup, then reconnects in the background while the
+// broker is unreachable so that the health check recovers on its own
+fn spawn_iggy_monitor(config: Arc<Config>) {
+    tokio::spawn(async move {
+        let pool = config.iggy_pool();
+        let mut interval = tokio::time::interval(Duration::from_secs(30));
+        loop {
+            interval.tick().await;
+            if pool.health().connected {
+                continue;
+            }
+            if let Err(e) = pool.connect().await {
+                warn!(
+                    "Iggy broker at {} unreachable, actions will fail until it is back: {}",
+                    pool.config().address,
+                    e
+                );
+            }
+        }
+    });
+}
+
+const OUTBOX_PURGE_PERIOD: Duration = Duration::from_secs(3600);
+
+// Retries failed action dispatches once their backoff has elapsed, and purges
+// expired sent messages every hour
+pub(crate) fn spawn_outbox_retry(config: Arc<Config>) {
+    tokio::spawn(async move {
+        let period = config.actions.outbox.initial_backoff_secs.max(1);
+        let mut interval = tokio::time::interval(Duration::from_secs(period));
+        let mut last_purge: Option<tokio::time::Instant> = None;
+        loop {
+            interval.tick().await;
+            match config.outbox().process_due(&config).await {
+                Ok(0) => {}
+                Ok(delivered) => info!("Delivered {} queued actions", delivered),
+                Err(e) => error!("Failed to process the action outbox: {}", e),
+            }
+            if last_purge.is_some_and(|at| at.elapsed() < OUTBOX_PURGE_PERIOD) {
+                continue;
+            }
+            last_purge = Some(tokio::time::Instant::now());
+            match config.outbox().purge(None).await {
+                Ok(0) => {}
+                Ok(purged) => info!("Purged {} sent actions from the outbox", purged),
+                Err(e) => error!("Failed to purge the action outbox: {}", e),
+            }
+        }
+    });
+}
@@ -11,6 +11,7 @@ pub fn create_endpoint_match(
     EndpointMatch {
         endpoint_id: result.endpoint_id.clone(),
         similarity: similarity as f64,
+        confidence: result.confidence as f64,
         parameters: result.parameters.clone(),
         is_negated,
         missing_required: result
@@ -1,15 +1,17 @@
-use crate::config::Config;
+use crate::config::{Config, OptionalParameterPolicy, SearchOptions};
 use crate::database::vector_db::VectorDB;
 use crate::grpc::matcher_service::matcher::interactive_response::Response::MatchResult;
-use crate::grpc::matcher_service::matcher::{EndpointMatch, InteractiveResponse, MatchResponse};
+use crate::grpc::matcher_service::matcher::{
+    Cancelled, EndpointMatch, InteractiveResponse, MatchResponse, ParameterAccepted, ParameterInfo,
+    ParameterPrompt, ParameterRejected, SkipParameter,
+};
 use crate::interaction::state::InteractionState;
-use crate::preprocessing::preprocess_query::preprocess_query;
 use tokio::sync::mpsc::Sender;
-use tokio::time::Duration;
 use tonic::Status;
 use tracing::error;
 
 use super::endpoint::create_endpoint_match;
+use super::validate_parameter::validate_parameter;
 use crate::grpc::matcher_service::matcher::interactive_response::Response as InteractiveResponseType;
 
 pub async fn handle_initial_query(
@@ -19,16 +21,17 @@ pub async fn handle_initial_query(
     config: &Config,
     tx: &Sender<Result<InteractiveResponse, Status>>,
 ) -> Option<InteractionState> {
-    let processed = preprocess_query(query, language);
-
     match db
-        .search_similar(&processed.cleaned_text, language, 1, config)
+        .search_similar_with_options(query, language, 1, config, &SearchOptions::default())
         .await
     {
-        Ok((results, similarity)) => {
-            if let Some(result) = results.first() {
-                let endpoint_match =
-                    create_endpoint_match(result, processed.is_negated, similarity);
+        Ok(outcome) => {
+            if let Some(result) = outcome.results.first() {
+                let endpoint_match = create_endpoint_match(
+                    result,
+                    outcome.processed.is_negated,
+                    outcome.best_similarity,
+                );
 
                 // Send confirmation prompt
                 if let Err(e) = send_confirmation_prompt(&endpoint_match, tx).await {
@@ -57,37 +60,149 @@ pub async fn handle_initial_query(
 pub async fn handle_confirmation(
     confirmed: bool,
     state: InteractionState,
+    config: &Config,
     tx: &Sender<Result<InteractiveResponse, Status>>,
 ) -> Option<InteractionState> {
     match state {
         InteractionState::AwaitingConfirmation { endpoint_match } => {
             if confirmed {
-                if has_missing_parameters(&endpoint_match) {
-                    if let Err(e) = send_first_parameter_prompt(&endpoint_match, tx).await {
-                        error!("Failed to send parameter prompt: {}", e);
-                        return None;
-                    }
-                    Some(InteractionState::CollectingParameters {
-                        endpoint_match,
-                        collected_parameters: Default::default(),
-                    })
-                } else {
-                    if let Err(e) = send_final_match_response(&endpoint_match, tx).await {
-                        error!("Failed to send final match: {}", e);
-                        return None;
-                    }
-                    Some(InteractionState::Completed { endpoint_match })
-                }
+                let optional = optional_parameters_to_ask(&endpoint_match, config);
+                prompt_next_or_complete(
+                    endpoint_match,
+                    Default::default(),
+                    Vec::new(),
+                    optional,
+                    tx,
+                )
+                .await
             } else {
-                if let Err(e) = send_cancelled_response(tx).await {
+                let state = InteractionState::AwaitingConfirmation { endpoint_match };
+                if let Err(e) = send_cancelled_response(Some(&state), tx).await {
                     error!("Failed to send cancelled response: {}", e);
                 }
                 None
             }
         }
-        _ => {
+        state => {
             error!("Received confirmation in invalid state");
-            None
+            send_invalid_command(
+                "confirmation_response",
+                "no confirmation is pending",
+                Some(&state),
+                tx,
+            )
+            .await;
+            Some(state)
+        }
+    }
+}
+
+/// Cancel is valid in every state: whatever was in progress is dropped.
+pub async fn handle_cancel(
+    state: Option<InteractionState>,
+    tx: &Sender<Result<InteractiveResponse, Status>>,
+) -> Option<InteractionState> {
+    if let Some(state) = &state {
+        println!("🛑 SERVER: Cancelled while {}", state.name());
+    }
+    if let Err(e) = send_cancelled_response(state.as_ref(), tx).await {
+        error!("Failed to send cancelled response: {}", e);
+    }
+    None
+}
+
+/// Back asks the last answered parameter again, or the confirmation when no
+/// parameter was answered yet. The confirmation has nothing before it.
+pub async fn handle_back(
+    state: Option<InteractionState>,
+    tx: &Sender<Result<InteractiveResponse, Status>>,
+) -> Option<InteractionState> {
+    match state {
+        Some(InteractionState::AwaitingConfirmation { endpoint_match }) => {
+            if let Err(e) = send_confirmation_prompt(&endpoint_match, tx).await {
+                error!("Failed to send confirmation prompt: {}", e);
+                return None;
+            }
+            Some(InteractionState::AwaitingConfirmation { endpoint_match })
+        }
+        Some(InteractionState::CollectingParameters {
+            mut endpoint_match,
+            mut collected_parameters,
+            mut answered,
+            mut optional,
+        }) => match answered.pop() {
+            Some(previous) => {
+                collected_parameters.remove(&previous.name);
+                endpoint_match.parameters.remove(&previous.name);
+                if previous.required {
+                    endpoint_match.missing_required.insert(0, previous);
+                } else {
+                    optional.insert(0, previous);
+                }
+                if let Err(e) = send_next_parameter_prompt(&endpoint_match, &optional, tx).await {
+                    error!("Failed to send parameter prompt: {}", e);
+                    return None;
+                }
+                Some(InteractionState::CollectingParameters {
+                    endpoint_match,
+                    collected_parameters,
+                    answered,
+                    optional,
+                })
+            }
+            None => {
+                if let Err(e) = send_confirmation_prompt(&endpoint_match, tx).await {
+                    error!("Failed to send confirmation prompt: {}", e);
+                    return None;
+                }
+                Some(InteractionState::AwaitingConfirmation { endpoint_match })
+            }
+        },
+        state => {
+            send_invalid_command("back", "nothing to go back to", state.as_ref(), tx).await;
+            state
+        }
+    }
+}
+
+/// Tells the client its request was ignored because of the current state.
+pub async fn send_invalid_command(
+    command: &str,
+    reason: &str,
+    state: Option<&InteractionState>,
+    tx: &Sender<Result<InteractiveResponse, Status>>,
+) {
+    let invalid = crate::grpc::matcher_service::matcher::InvalidCommand {
+        command: command.to_string(),
+        reason: reason.to_string(),
+        state: state.map_or("idle", InteractionState::name).to_string(),
+    };
+    if let Err(e) = tx
+        .send(Ok(InteractiveResponse {
+            response: Some(InteractiveResponseType::InvalidCommand(invalid)),
+        }))
+        .await
+    {
+        error!("Failed to send invalid command response: {}", e);
+    }
+}
+
+/// Sends again the prompt `state` is waiting on, after a session is resumed.
+pub async fn resend_pending_prompt(
+    state: &InteractionState,
+    tx: &Sender<Result<InteractiveResponse, Status>>,
+) -> Result<(), Status> {
+    match state {
+        InteractionState::AwaitingConfirmation { endpoint_match } => {
+            send_confirmation_prompt(endpoint_match, tx).await
+        }
+        InteractionState::CollectingParameters {
+            endpoint_match,
+            optional,
+            ..
+        } => send_next_parameter_prompt(endpoint_match, optional, tx).await,
+        InteractionState::Completed { endpoint_match } => {
+            send_final_match_response(endpoint_match, tx).await
         }
     }
 }
@@ -97,10 +212,6 @@ async fn send_confirmation_prompt(
     endpoint_match: &EndpointMatch,
     tx: &Sender<Result<InteractiveResponse, Status>>,
 ) -> Result<(), Status> {
-    println!("🔄 SERVER: Processing query before sending confirmation...");
-
-    // Simulate server processing time
-    tokio::time::sleep(Duration::from_secs(2)).await;
     let confirmation = crate::grpc::matcher_service::matcher::ConfirmationPrompt {
         matched_endpoint: Some(endpoint_match.clone()),
     };
@@ -116,35 +227,51 @@ async fn send_confirmation_prompt(
         .await;
     //.map_err(|e| Status::internal(format!("Failed to send confirmation prompt: {}", e)))
 
-    // Add delay after sending
-    tokio::time::sleep(Duration::from_millis(5000)).await;
-
     Ok(())
 }
 
-async fn send_first_parameter_prompt(
+// Prompts the first missing required parameter, then the optional ones
+async fn send_next_parameter_prompt(
     endpoint_match: &EndpointMatch,
+    optional: &[ParameterInfo],
     tx: &Sender<Result<InteractiveResponse, Status>>,
 ) -> Result<(), Status> {
-    if let Some(first_param) = endpoint_match.missing_required.first() {
-        let parameter = crate::grpc::matcher_service::matcher::ParameterPrompt {
-            parameter_name: first_param.name.clone(),
-            description: first_param.description.clone(),
-            required: true,
-            endpoint_id: endpoint_match.endpoint_id.clone(),
-        };
-
+    if let Some(parameter) = next_parameter_prompt(endpoint_match, optional) {
         let _ = tx
             .send(Ok(InteractiveResponse {
                 response: Some(InteractiveResponseType::ParameterPrompt(parameter)),
             }))
             .await;
         //.map_err(|e| Status::internal(format!("Failed to send parameter prompt: {}", e)))
+    }
+    Ok(())
+}
 
-        tokio::time::sleep(Duration::from_millis(5000)).await;
-        Ok(())
-    } else {
-        Ok(())
+fn next_parameter_prompt(
+    endpoint_match: &EndpointMatch,
+    optional: &[ParameterInfo],
+) -> Option<ParameterPrompt> {
+    endpoint_match
+        .missing_required
+        .first()
+        .map(|p| parameter_prompt(endpoint_match, p, true))
+        .or_else(|| {
+            optional
+                .first()
+                .map(|p| parameter_prompt(endpoint_match, p, false))
+        })
+}
+
+fn parameter_prompt(
+    endpoint_match: &EndpointMatch,
+    parameter: &ParameterInfo,
+    required: bool,
+) -> ParameterPrompt {
+    ParameterPrompt {
+        parameter_name: parameter.name.clone(),
+        description: parameter.description.clone(),
+        required,
+        endpoint_id: endpoint_match.endpoint_id.clone(),
     }
 }
 
@@ -156,6 +283,8 @@ async fn send_final_match_response(
         matches: vec![endpoint_match.clone()],
         score: 1.0,
         has_matches: true,
+        debug_info: None,
+        intents: vec![],
     };
 
     // Use try_send or check if channel is still open
@@ -174,16 +303,15 @@ async fn send_final_match_response(
 }
 
 async fn send_cancelled_response(
+    state: Option<&InteractionState>,
     tx: &Sender<Result<InteractiveResponse, Status>>,
 ) -> Result<(), Status> {
-    let response = MatchResponse {
-        matches: vec![],
-        score: 0.0,
-        has_matches: false,
+    let cancelled = Cancelled {
+        state: state.map_or("idle", InteractionState::name).to_string(),
     };
 
     tx.send(Ok(InteractiveResponse {
-        response: Some(MatchResult(response)),
+        response: Some(InteractiveResponseType::Cancelled(cancelled)),
     }))
     .await
     .map_err(|e| Status::internal(format!("Failed to send cancelled response: {}", e)))
@@ -196,6 +324,8 @@ async fn send_no_matches_response(
         matches: vec![],
         score: 0.0,
         has_matches: false,
+        debug_info: None,
+        intents: vec![],
     };
 
     tx.send(Ok(InteractiveResponse {
@@ -205,92 +335,269 @@ async fn send_no_matches_response(
     .map_err(|e| Status::internal(format!("Failed to send no matches response: {}", e)))
 }
 
-fn has_missing_parameters(endpoint_match: &EndpointMatch) -> bool {
-    !endpoint_match.missing_required.is_empty()
+// Missing optional parameters of the endpoint, if its policy is to ask for them
+fn optional_parameters_to_ask(
+    endpoint_match: &EndpointMatch,
+    config: &Config,
+) -> Vec<ParameterInfo> {
+    let policy = config
+        .endpoints
+        .iter()
+        .find(|e| e.id == endpoint_match.endpoint_id)
+        .map(|e| e.optional_parameters)
+        .unwrap_or_default();
+    match policy {
+        OptionalParameterPolicy::Never => Vec::new(),
+        OptionalParameterPolicy::Ask | OptionalParameterPolicy::AskOnce => {
+            endpoint_match.missing_optional.clone()
+        }
+    }
 }
 
+// Prompts the next parameter, or sends the final match once nothing is left to ask
+async fn prompt_next_or_complete(
+    endpoint_match: EndpointMatch,
+    collected_parameters: std::collections::HashMap<String, String>,
+    answered: Vec<ParameterInfo>,
+    optional: Vec<ParameterInfo>,
+    tx: &Sender<Result<InteractiveResponse, Status>>,
+) -> Option<InteractionState> {
+    if !endpoint_match.missing_required.is_empty() || !optional.is_empty() {
+        println!("📤 SERVER: Requesting next parameter");
+        if let Err(e) = send_next_parameter_prompt(&endpoint_match, &optional, tx).await {
+            error!("Failed to send parameter prompt: {}", e);
+            return None;
+        }
+        Some(InteractionState::CollectingParameters {
+            endpoint_match,
+            collected_parameters,
+            answered,
+            optional,
+        })
+    } else {
+        println!("✅ SERVER: All parameters collected");
+        if let Err(e) = send_final_match_response(&endpoint_match, tx).await {
+            error!("Failed to send final match: {}", e);
+            return None;
+        }
+        Some(InteractionState::Completed { endpoint_match })
+    }
+}
+
+/// Declines an optional parameter. Required parameters cannot be skipped;
+/// with the ask_once policy, skipping one skips every remaining optional parameter.
+pub async fn handle_skip_parameter(
+    skip: SkipParameter,
+    state: Option<InteractionState>,
+    config: &Config,
+    tx: &Sender<Result<InteractiveResponse, Status>>,
+) -> Option<InteractionState> {
+    match state {
+        Some(InteractionState::CollectingParameters {
+            endpoint_match,
+            collected_parameters,
+            answered,
+            mut optional,
+        }) if optional.iter().any(|p| p.name == skip.parameter_name) => {
+            println!("⏭️ SERVER: Skipping parameter '{}'", skip.parameter_name);
+            let ask_once = config
+                .endpoints
+                .iter()
+                .find(|e| e.id == endpoint_match.endpoint_id)
+                .is_some_and(|e| e.optional_parameters == OptionalParameterPolicy::AskOnce);
+            if ask_once {
+                optional.clear();
+            } else {
+                optional.retain(|p| p.name != skip.parameter_name);
+            }
+            prompt_next_or_complete(endpoint_match, collected_parameters, answered, optional, tx)
+                .await
+        }
+        state => {
+            let reason = match &state {
+                Some(InteractionState::CollectingParameters { endpoint_match, .. })
+                    if endpoint_match
+                        .missing_required
+                        .iter()
+                        .any(|p| p.name == skip.parameter_name) =>
+                {
+                    "required parameters cannot be skipped"
+                }
+                _ => "this parameter is not being asked",
+            };
+            send_invalid_command("skip_parameter", reason, state.as_ref(), tx).await;
+            state
+        }
+    }
+}
+
+/// Stores a parameter value once it has been validated against the endpoint
+/// definition. Rejected values leave the state unchanged and are prompted again.
 pub async fn handle_parameter_value(
     parameter_value: crate::grpc::matcher_service::matcher::ParameterValue,
     state: InteractionState,
+    config: &Config,
     tx: &Sender<Result<InteractiveResponse, Status>>,
 ) -> Option<InteractionState> {
     match state {
         InteractionState::CollectingParameters {
             mut endpoint_match,
             mut collected_parameters,
+            mut answered,
+            mut optional,
         } => {
             println!(
                 "🔄 SERVER: Processing parameter value: {}",
                 parameter_value.parameter_name
             );
 
-            // Store the received parameter
-            collected_parameters.insert(
-                parameter_value.parameter_name.clone(),
-                parameter_value.value,
-            );
+            let definition = config
+                .endpoints
+                .iter()
+                .find(|e| e.id == endpoint_match.endpoint_id)
+                .and_then(|e| {
+                    e.parameters
+                        .iter()
+                        .find(|p| p.name == parameter_value.parameter_name)
+                });
+            let validated = match definition {
+                Some(definition) => validate_parameter(definition, &parameter_value.value),
+                None => Err(format!(
+                    "endpoint '{}' has no parameter '{}'",
+                    endpoint_match.endpoint_id, parameter_value.parameter_name
+                )),
+            };
+            let value = match validated {
+                Ok(value) => value,
+                Err(reason) => {
+                    println!(
+                        "❌ SERVER: Parameter '{}' rejected: {}",
+                        parameter_value.parameter_name, reason
+                    );
+                    if let Err(e) = send_parameter_rejected_response(
+                        &parameter_value,
+                        &reason,
+                        &endpoint_match,
+                        &optional,
+                        tx,
+                    )
+                    .await
+                    {
+                        error!("Failed to send parameter rejection: {}", e);
+                        return None;
+                    }
+                    return Some(InteractionState::CollectingParameters {
+                        endpoint_match,
+                        collected_parameters,
+                        answered,
+                        optional,
+                    });
+                }
+            };
 
-            // Update endpoint parameters
-            endpoint_match.parameters = collected_parameters.clone();
-            // Filter out the parameter we just received
+            // Parameters extracted from the query are kept alongside the answers
+            collected_parameters.insert(parameter_value.parameter_name.clone(), value.clone());
             endpoint_match
+                .parameters
+                .insert(parameter_value.parameter_name.clone(), value.clone());
+            // Filter out the parameter we just received
+            if let Some(idx) = endpoint_match
                 .missing_required
-                .retain(|p| p.name != parameter_value.parameter_name);
+                .iter()
+                .position(|p| p.name == parameter_value.parameter_name)
+            {
+                answered.push(endpoint_match.missing_required.remove(idx));
+            } else if let Some(idx) = optional
+                .iter()
+                .position(|p| p.name == parameter_value.parameter_name)
+            {
+                answered.push(optional.remove(idx));
+            }
 
             // Send parameter accepted confirmation
             if let Err(e) =
-                send_parameter_accepted_response(&parameter_value.parameter_name, tx).await
+                send_parameter_accepted_response(&parameter_value.parameter_name, &value, tx).await
             {
                 error!("Failed to send parameter acceptance: {}", e);
                 return None;
             }
 
-            // Add a small delay before next action
-            tokio::time::sleep(Duration::from_millis(1000)).await;
-
             // Check if we need more parameters
-            if !endpoint_match.missing_required.is_empty() {
-                println!("📤 SERVER: Requesting next parameter");
-                if let Err(e) = send_first_parameter_prompt(&endpoint_match, tx).await {
-                    error!("Failed to send parameter prompt: {}", e);
-                    return None;
-                }
-                Some(InteractionState::CollectingParameters {
-                    endpoint_match,
-                    collected_parameters,
-                })
-            } else {
-                println!("✅ SERVER: All required parameters collected");
-                if let Err(e) = send_final_match_response(&endpoint_match, tx).await {
-                    error!("Failed to send final match: {}", e);
-                    return None;
-                }
-                Some(InteractionState::Completed { endpoint_match })
-            }
+            prompt_next_or_complete(endpoint_match, collected_parameters, answered, optional, tx)
+                .await
         }
-        _ => {
-            error!("Received parameter value in invalid state");
+        // Already dispatched: handing it back would dispatch the action again
+        state @ InteractionState::Completed { .. } => {
+            error!("Received parameter value after completion");
+            send_invalid_command(
+                "parameter_value",
+                "the interaction is already completed",
+                Some(&state),
+                tx,
+            )
+            .await;
             None
         }
+        state => {
+            error!("Received parameter value in invalid state");
+            send_invalid_command(
+                "parameter_value",
+                "no parameter is being collected",
+                Some(&state),
+                tx,
+            )
+            .await;
+            Some(state)
+        }
     }
 }
 
+// Re-prompts the rejected parameter when it is pending, otherwise the next one
+async fn send_parameter_rejected_response(
+    parameter_value: &crate::grpc::matcher_service::matcher::ParameterValue,
+    reason: &str,
+    endpoint_match: &EndpointMatch,
+    optional: &[ParameterInfo],
+    tx: &Sender<Result<InteractiveResponse, Status>>,
+) -> Result<(), Status> {
+    let pending = endpoint_match
+        .missing_required
+        .iter()
+        .map(|p| (p, true))
+        .chain(optional.iter().map(|p| (p, false)))
+        .find(|(p, _)| p.name == parameter_value.parameter_name);
+    let prompt = match pending {
+        Some((parameter, required)) => Some(parameter_prompt(endpoint_match, parameter, required)),
+        None => next_parameter_prompt(endpoint_match, optional),
+    };
+
+    let rejected = ParameterRejected {
+        parameter_name: parameter_value.parameter_name.clone(),
+        value: parameter_value.value.clone(),
+        reason: reason.to_string(),
+        prompt,
+    };
+    tx.send(Ok(InteractiveResponse {
+        response: Some(InteractiveResponseType::ParameterRejected(rejected)),
+    }))
+    .await
+    .map_err(|e| Status::internal(format!("Failed to send parameter rejection: {}", e)))
+}
+
 async fn send_parameter_accepted_response(
     parameter_name: &str,
+    value: &str,
     tx: &Sender<Result<InteractiveResponse, Status>>,
 ) -> Result<(), Status> {
-    let response = MatchResponse {
-        matches: vec![],
-        score: 1.0,
-        has_matches: true,
-        // You might need to add a message field to your proto if you want to include custom messages
-        // For now, we're using the existing fields
+    let accepted = ParameterAccepted {
+        parameter_name: parameter_name.to_string(),
+        message: format!("{} = {}", parameter_name, value),
     };
 
     println!("✅ SERVER: Parameter '{}' accepted", parameter_name);
 
     tx.send(Ok(InteractiveResponse {
-        response: Some(InteractiveResponseType::MatchResult(response)),
+        response: Some(InteractiveResponseType::ParameterAccepted(accepted)),
     }))
     .await
     .map_err(|e| Status::internal(format!("Failed to send parameter acceptance: {}", e)))
@@ -1,3 +1,5 @@
 pub mod endpoint;
 pub mod handlers;
+pub mod session_store;
 pub mod state;
+pub mod validate_parameter;
@@ -0,0 +1,281 @@
+use anyhow::{Context, Result as AnyhowResult};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use super::state::InteractionState;
+use crate::config::{SessionStoreKind, SessionsConfig};
+
+/// An interactive conversation that can outlive the stream it started on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    // Query that started the session
+    #[serde(default)]
+    pub query: String,
+    pub language: String,
+    pub state: InteractionState,
+    // Unix timestamp (seconds) of the last request
+    pub updated_at: u64,
+}
+
+impl Session {
+    pub fn new(query: &str, language: &str, state: InteractionState) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            query: query.to_string(),
+            language: language.to_string(),
+            state,
+            updated_at: now(),
+        }
+    }
+
+    pub fn touch(&mut self) {
+        self.updated_at = now();
+    }
+
+    pub fn is_expired(&self, idle_timeout: Duration) -> bool {
+        now().saturating_sub(self.updated_at) > idle_timeout.as_secs()
+    }
+}
+
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    /// Returns the session, or None if it does not exist or has expired.
+    async fn load(&self, session_id: &str) -> AnyhowResult<Option<Session>>;
+    async fn save(&self, session: &Session) -> AnyhowResult<()>;
+    async fn remove(&self, session_id: &str) -> AnyhowResult<()>;
+    /// Drops every session idle for longer than the timeout, returns how many.
+    async fn purge_expired(&self) -> AnyhowResult<usize>;
+}
+
+pub fn create_session_store(config: &SessionsConfig) -> Arc<dyn SessionStore> {
+    let idle_timeout = Duration::from_secs(config.idle_timeout_secs);
+    match config.store {
+        SessionStoreKind::Memory => Arc::new(InMemorySessionStore::new(idle_timeout)),
+        SessionStoreKind::File => Arc::new(FileSessionStore::new(&config.path, idle_timeout)),
+    }
+}
+
+pub struct InMemorySessionStore {
+    idle_timeout: Duration,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl InMemorySessionStore {
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            idle_timeout,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl SessionStore for InMemorySessionStore {
+    async fn load(&self, session_id: &str) -> AnyhowResult<Option<Session>> {
+        let mut sessions = self.sessions.lock().await;
+        match sessions.get(session_id) {
+            Some(session) if session.is_expired(self.idle_timeout) => {
+                sessions.remove(session_id);
+                Ok(None)
+            }
+            session => Ok(session.cloned()),
+        }
+    }
+
+    async fn save(&self, session: &Session) -> AnyhowResult<()> {
+        self.sessions
+            .lock()
+            .await
+            .insert(session.id.clone(), session.clone());
+        Ok(())
+    }
+
+    async fn remove(&self, session_id: &str) -> AnyhowResult<()> {
+        self.sessions.lock().await.remove(session_id);
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> AnyhowResult<usize> {
+        let mut sessions = self.sessions.lock().await;
+        let before = sessions.len();
+        sessions.retain(|_, session| !session.is_expired(self.idle_timeout));
+        Ok(before - sessions.len())
+    }
+}
+
+/// Stores each session as `<id>.json` in a directory, so sessions survive
+/// a server restart.
+pub struct FileSessionStore {
+    directory: PathBuf,
+    idle_timeout: Duration,
+}
+
+impl FileSessionStore {
+    pub fn new(directory: &str, idle_timeout: Duration) -> Self {
+        Self {
+            directory: PathBuf::from(directory),
+            idle_timeout,
+        }
+    }
+
+    fn session_path(&self, session_id: &str) -> AnyhowResult<PathBuf> {
+        // Session ids come from clients, keep them inside the directory
+        if session_id.is_empty()
+            || !session_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(anyhow::anyhow!("Invalid session id '{}'", session_id));
+        }
+        Ok(self.directory.join(format!("{}.json", session_id)))
+    }
+}
+
+#[async_trait]
+impl SessionStore for FileSessionStore {
+    async fn load(&self, session_id: &str) -> AnyhowResult<Option<Session>> {
+        let path = self.session_path(session_id)?;
+        let content = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+        };
+        let session: Session = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid session file {:?}", path))?;
+
+        if session.is_expired(self.idle_timeout) {
+            self.remove(session_id).await?;
+            return Ok(None);
+        }
+        Ok(Some(session))
+    }
+
+    async fn save(&self, session: &Session) -> AnyhowResult<()> {
+        let path = self.session_path(&session.id)?;
+        tokio::fs::create_dir_all(&self.directory).await?;
+
+        // Write then rename so that a crash never leaves a truncated session
+        let tmp_path = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, serde_json::to_vec_pretty(session)?).await?;
+        tokio::fs::rename(&tmp_path, &path)
+            .await
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    async fn remove(&self, session_id: &str) -> AnyhowResult<()> {
+        let path = self.session_path(session_id)?;
+        match tokio::fs::remove_file(&path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Failed to remove {:?}", path))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn purge_expired(&self) -> AnyhowResult<usize> {
+        let mut entries = match tokio::fs::read_dir(&self.directory).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut purged = 0;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let expired = match tokio::fs::read_to_string(&path).await {
+                Ok(content) => serde_json::from_str::<Session>(&content)
+                    .map_or(true, |session| session.is_expired(self.idle_timeout)),
+                Err(e) => {
+                    warn!("Failed to read session file {:?}: {}", path, e);
+                    false
+                }
+            };
+            if expired {
+                tokio::fs::remove_file(&path).await?;
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc::matcher_service::matcher::EndpointMatch;
+
+    fn session() -> Session {
+        Session::new(
+            "envoyer un mail",
+            "fr",
+            InteractionState::CollectingParameters {
+                endpoint_match: EndpointMatch {
+                    endpoint_id: "send_email".to_string(),
+                    similarity: 0.9,
+                    ..Default::default()
+                },
+                collected_parameters: HashMap::from([(
+                    "email".to_string(),
+                    "toto@gmail.com".to_string(),
+                )]),
+                answered: vec![],
+                optional: vec![],
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_file_store_round_trip() {
+        let directory =
+            std::env::temp_dir().join(format!("matcher-sessions-{}", uuid::Uuid::new_v4()));
+        let store = FileSessionStore::new(directory.to_str().unwrap(), Duration::from_secs(60));
+        let session = session();
+
+        store.save(&session).await.unwrap();
+        let loaded = store.load(&session.id).await.unwrap().unwrap();
+        match loaded.state {
+            InteractionState::CollectingParameters {
+                endpoint_match,
+                collected_parameters,
+                ..
+            } => {
+                assert_eq!(endpoint_match.endpoint_id, "send_email");
+                assert_eq!(collected_parameters["email"], "toto@gmail.com");
+            }
+            state => panic!("unexpected state {:?}", state),
+        }
+
+        store.remove(&session.id).await.unwrap();
+        assert!(store.load(&session.id).await.unwrap().is_none());
+        assert!(store.load("../endpoints").await.is_err());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_idle_sessions_expire() {
+        let store = InMemorySessionStore::new(Duration::from_secs(60));
+        let mut session = session();
+        session.updated_at -= 120;
+        store.save(&session).await.unwrap();
+
+        assert!(store.load(&session.id).await.unwrap().is_none());
+        store.save(&session).await.unwrap();
+        assert_eq!(store.purge_expired().await.unwrap(), 1);
+    }
+}
@@ -1,7 +1,9 @@
-use crate::grpc::matcher_service::matcher::EndpointMatch;
+use crate::grpc::matcher_service::matcher::{EndpointMatch, ParameterInfo};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
 pub enum InteractionState {
     AwaitingConfirmation {
         endpoint_match: EndpointMatch,
@@ -9,6 +11,12 @@ pub enum InteractionState {
     CollectingParameters {
         endpoint_match: EndpointMatch,
         collected_parameters: HashMap<String, String>,
+        // Parameters answered so far, in order, so that Back can ask them again
+        #[serde(default)]
+        answered: Vec<ParameterInfo>,
+        // Optional parameters still to offer, in order, once the required ones are known
+        #[serde(default)]
+        optional: Vec<ParameterInfo>,
     },
     Completed {
         endpoint_match: EndpointMatch, // Kept if needed for final response
@@ -20,6 +28,14 @@ impl InteractionState {
         InteractionState::AwaitingConfirmation { endpoint_match }
     }
 
+    pub fn name(&self) -> &'static str {
+        match self {
+            InteractionState::AwaitingConfirmation { .. } => "awaiting_confirmation",
+            InteractionState::CollectingParameters { .. } => "collecting_parameters",
+            InteractionState::Completed { .. } => "completed",
+        }
+    }
+
     //pub fn get_endpoint_match(&self) -> &EndpointMatch {
     //    match self {
     //        InteractionState::AwaitingConfirmation { endpoint_match } => endpoint_match,
@@ -0,0 +1,83 @@
+use crate::config::{Parameter, ParameterKind};
+use crate::preprocessing::EMAIL_REGEX;
+
+/// Checks a value given interactively for `parameter` and returns it
+/// normalised, or the reason it was rejected.
+pub fn validate_parameter(parameter: &Parameter, value: &str) -> Result<String, String> {
+    let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    if value.is_empty() {
+        return Err("value is empty".to_string());
+    }
+
+    match parameter.kind {
+        ParameterKind::Text => Ok(value),
+        ParameterKind::Email => {
+            let email = value.to_lowercase();
+            match EMAIL_REGEX.find(&email) {
+                Some(m) if m.start() == 0 && m.end() == email.len() => Ok(email),
+                _ => Err(format!("'{}' is not a valid email address", value)),
+            }
+        }
+        ParameterKind::Integer => value
+            .parse::<i64>()
+            .map(|n| n.to_string())
+            .map_err(|_| format!("'{}' is not a whole number", value)),
+        ParameterKind::Number => {
+            // Accept the French decimal comma
+            let number = value.replace(',', ".");
+            number
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .map(|_| number)
+                .ok_or_else(|| format!("'{}' is not a number", value))
+        }
+        ParameterKind::Boolean => match value.to_lowercase().as_str() {
+            "true" | "yes" | "y" | "oui" | "o" | "1" => Ok("true".to_string()),
+            "false" | "no" | "n" | "non" | "0" => Ok("false".to_string()),
+            _ => Err(format!("'{}' is not yes or no", value)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameter(kind: ParameterKind) -> Parameter {
+        Parameter {
+            name: "value".to_string(),
+            description: String::new(),
+            required: true,
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_values_are_normalised() {
+        assert_eq!(
+            validate_parameter(&parameter(ParameterKind::Email), " Toto@Gmail.com "),
+            Ok("toto@gmail.com".to_string())
+        );
+        assert_eq!(
+            validate_parameter(&parameter(ParameterKind::Number), "3,5"),
+            Ok("3.5".to_string())
+        );
+        assert_eq!(
+            validate_parameter(&parameter(ParameterKind::Boolean), "Oui"),
+            Ok("true".to_string())
+        );
+        assert_eq!(
+            validate_parameter(&parameter(ParameterKind::Text), "  mon   titre "),
+            Ok("mon titre".to_string())
+        );
+    }
+
+    #[test]
+    fn test_invalid_values_are_rejected() {
+        assert!(validate_parameter(&parameter(ParameterKind::Email), "toto").is_err());
+        assert!(validate_parameter(&parameter(ParameterKind::Email), "a toto@gmail.com").is_err());
+        assert!(validate_parameter(&parameter(ParameterKind::Integer), "12.5").is_err());
+        assert!(validate_parameter(&parameter(ParameterKind::Text), "   ").is_err());
+    }
+}
@@ -1,8 +1,14 @@
+mod actions;
+mod batch;
+mod calibration;
 mod candle;
 mod cli;
 mod config;
 mod constants;
+mod conversation;
 mod database;
+mod dataset;
+mod evaluation;
 mod filters;
 mod grpc;
 mod interaction;
@@ -10,16 +16,24 @@ mod messaging;
 mod preprocessing;
 mod process_search_results;
 mod search_operations;
+mod worker;
 
 #[cfg(test)]
 mod tests;
 // Re-export everything that main.rs needs
 pub use crate::database::vector_db::VectorDB;
+pub use actions::outbox_command::run_outbox_command;
+pub use batch::run_batch::run_batch;
+pub use calibration::{run_calibration::run_calibration, Calibration};
 pub use candle::load_model::load_model;
 pub use candle::MODEL_PATH;
-pub use cli::parse_args;
-pub use config::Config;
+pub use cli::{parse_args, Command};
+pub use config::{CalibrationMethod, Config};
 pub use constants::*;
 pub use database::initialization::table_init::initialize_table;
+pub use dataset::load_labelled_queries;
+pub use evaluation::run_evaluation::run_evaluation;
+pub use evaluation::tune_thresholds::{run_tuning, ThresholdOverrides, TuningTarget};
 pub use grpc::start_grpc_server::start_grpc_server;
 pub use process_search_results::process_search_results;
+pub use worker::consume_requests::consume_requests;
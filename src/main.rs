@@ -2,10 +2,12 @@ use anyhow::Result as AnyhowResult;
 use lancedb::connect;
 use matcher::initialize_table;
 use matcher::{
-    parse_args, process_search_results, start_grpc_server, Config, VectorDB, CONFIG_PATH,
-    MODEL_PATH,
+    consume_requests, load_labelled_queries, parse_args, process_search_results, run_batch,
+    run_calibration, run_evaluation, run_outbox_command, run_tuning, start_grpc_server, Command,
+    Config, ThresholdOverrides, TuningTarget, VectorDB, CONFIG_PATH, DB_PATH, MODEL_PATH,
 };
 use std::fs;
+use std::io::Write;
 use std::path::Path;
 use std::sync::Arc;
 use tracing::Level;
@@ -22,13 +24,106 @@ async fn main() -> AnyhowResult<()> {
     println!("Loading model from: {}", MODEL_PATH);
     let config = Arc::new(Config::load_from_yaml(CONFIG_PATH)?);
 
+    let db_path = DB_PATH;
+    match args.command {
+        Some(Command::Calibrate { dataset, method }) => {
+            let db = VectorDB::new(db_path, None, false).await?;
+            let queries = load_labelled_queries(&dataset)?;
+            println!("Calibrating on {} labelled queries...", queries.len());
+            let calibration = run_calibration(&db, &config, &queries, method).await?;
+            calibration.save(db_path)?;
+            println!(
+                "Calibration saved to {:?}",
+                matcher::Calibration::path(db_path)
+            );
+            return Ok(());
+        }
+        Some(Command::Eval {
+            dataset,
+            json,
+            markdown,
+        }) => {
+            let db = Arc::new(VectorDB::new(db_path, None, false).await?);
+            let queries = load_labelled_queries(&dataset)?;
+            println!("Evaluating on {} labelled queries...", queries.len());
+            let report = run_evaluation(db, config, &queries).await?;
+            if let Some(path) = &json {
+                fs::write(path, serde_json::to_string_pretty(&report)?)?;
+                println!("JSON report written to {:?}", path);
+            }
+            match &markdown {
+                Some(path) => {
+                    fs::write(path, report.to_markdown())?;
+                    println!("Markdown report written to {:?}", path);
+                }
+                None => println!("\n{}", report.to_markdown()),
+            }
+            return Ok(());
+        }
+        Some(Command::Tune {
+            dataset,
+            target_precision,
+            target_coverage,
+            step,
+            write,
+            yes,
+        }) => {
+            let db = Arc::new(VectorDB::new(db_path, None, false).await?);
+            let queries = load_labelled_queries(&dataset)?;
+            println!("Tuning on {} labelled queries...", queries.len());
+            let target = match target_coverage {
+                Some(coverage) => TuningTarget::Coverage(coverage),
+                None => TuningTarget::Precision(target_precision),
+            };
+            let report = run_tuning(db, config, &queries, target, step).await?;
+            report.print();
+            if write && report.recommended.is_some() {
+                let path = ThresholdOverrides::path(Path::new(CONFIG_PATH));
+                if yes || confirm(&format!("Write the recommended thresholds to {:?}?", path))? {
+                    ThresholdOverrides::write(Path::new(CONFIG_PATH), &report)?;
+                    println!(
+                        "Thresholds written to {:?}, applied over {}",
+                        path, CONFIG_PATH
+                    );
+                } else {
+                    println!("Thresholds not written");
+                }
+            }
+            return Ok(());
+        }
+        Some(Command::Batch {
+            input,
+            output,
+            concurrency,
+        }) => {
+            let summary = run_batch(config, &input, &output, concurrency, &args.language).await?;
+            println!(
+                "Matched {} queries ({} failed), results written to {:?}",
+                summary.processed, summary.failed, output
+            );
+            if summary.labelled > 0 {
+                println!(
+                    "Best match correct for {}/{} labelled queries",
+                    summary.correct, summary.labelled
+                );
+            }
+            return Ok(());
+        }
+        Some(Command::Outbox { command }) => return run_outbox_command(command, &config).await,
+        None => {}
+    }
+
     // Ensure database directory exists
-    let db_path = "data/mydb";
     if args.reload {
         // Create or ensure the directory exists
         if Path::new(db_path).exists() {
             fs::remove_dir_all(db_path)?;
         }
+        // Fitted on the old index, so it no longer describes the new scores
+        let calibration_path = matcher::Calibration::path(db_path);
+        if calibration_path.exists() {
+            fs::remove_file(&calibration_path)?;
+        }
         fs::create_dir_all(db_path)?;
 
         println!("Initializing/reloading database...");
@@ -36,11 +131,16 @@ async fn main() -> AnyhowResult<()> {
         initialize_table(&connection, &config.endpoints).await?;
         println!("Database initialization complete");
 
-        if !args.server {
+        if !args.server && !args.consume {
             return Ok(());
         }
     }
 
+    if args.consume {
+        println!("Starting Iggy worker...");
+        return consume_requests(config).await;
+    }
+
     if args.server {
         println!("Starting gRPC server...");
         if let Err(e) = start_grpc_server(config).await {
@@ -54,8 +154,16 @@ async fn main() -> AnyhowResult<()> {
             let (results, _similarity) = db
                 .search_similar(&query, &args.language, 1, &config)
                 .await?;
-            process_search_results(results).await?;
+            process_search_results(results, &query, &args.language, &config).await?;
         }
     }
     Ok(())
 }
+
+fn confirm(question: &str) -> AnyhowResult<bool> {
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
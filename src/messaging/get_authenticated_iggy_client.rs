@@ -5,14 +5,41 @@ use iggy::clients::builder::IggyClientBuilder;
 use iggy::clients::client::IggyClient;
 use std::boxed::Box;
 
-pub async fn get_authenticated_iggy_client() -> AnyhowResult<Box<IggyClient>> {
-    let client = match IggyClientBuilder::new()
-        .with_tcp()
-        .with_server_address("iggy.mayorana.ch:8090".to_string())
-        .build()
-    {
+use crate::config::{default_iggy_credential, IggyConfig, IggyTransport};
+
+pub async fn get_authenticated_iggy_client(config: &IggyConfig) -> AnyhowResult<Box<IggyClient>> {
+    let builder = IggyClientBuilder::new();
+    let built = match config.transport {
+        IggyTransport::Tcp => {
+            let mut tcp = builder
+                .with_tcp()
+                .with_server_address(config.address.clone())
+                .with_tls_enabled(config.tls);
+            if let Some(domain) = &config.tls_domain {
+                tcp = tcp.with_tls_domain(domain.clone());
+            }
+            tcp.build()
+        }
+        IggyTransport::Quic => {
+            let mut quic = builder
+                .with_quic()
+                .with_server_address(config.address.clone());
+            if let Some(server_name) = &config.tls_domain {
+                quic = quic.with_server_name(server_name.clone());
+            }
+            quic.build()
+        }
+        IggyTransport::Http => builder
+            .with_http()
+            .with_api_url(config.address.clone())
+            .build(),
+    };
+    let client = match built {
         Ok(client) => {
-            println!("Successfully built Iggy client");
+            println!(
+                "Successfully built Iggy {:?} client for {}",
+                config.transport, config.address
+            );
             client
         }
         Err(e) => {
@@ -32,7 +59,14 @@ pub async fn get_authenticated_iggy_client() -> AnyhowResult<Box<IggyClient>> {
         }
     }
 
-    if let Err(e) = client.login_user("iggy", "iggy").await {
+    let (username, password) = config.credentials()?;
+    if username == default_iggy_credential() && password == default_iggy_credential() {
+        eprintln!(
+            "Warning: logging in to Iggy at {} with the default iggy/iggy credentials, set iggy.username and iggy.password (or their _file variants) outside local development",
+            config.address
+        );
+    }
+    if let Err(e) = client.login_user(&username, &password).await {
         eprintln!("Failed to login to Iggy: {}", e);
         return Err(anyhow!("Login failed: {}", e));
     }
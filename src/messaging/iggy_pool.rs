@@ -0,0 +1,171 @@
+use anyhow::{anyhow, Result as AnyhowResult};
+use iggy::clients::client::IggyClient;
+use iggy::clients::consumer::{AutoCommit, IggyConsumer};
+use iggy::clients::producer::IggyProducer;
+use iggy::messages::send_messages::Message;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use tokio::sync::{Mutex, OnceCell};
+use tracing::{info, warn};
+
+use super::get_authenticated_iggy_client::get_authenticated_iggy_client;
+use crate::config::IggyConfig;
+
+#[derive(Debug, Clone, Default)]
+pub struct IggyHealth {
+    pub connected: bool,
+    // Why the last connection or send failed, while disconnected
+    pub last_error: Option<String>,
+}
+
+type ProducerCell = Arc<OnceCell<Arc<IggyProducer>>>;
+
+/// One logged-in client shared by every Iggy action, connected on first use,
+/// with an initialised producer cached per (stream, topic). A failed send
+/// drops the client so the next one reconnects.
+pub struct IggyPool {
+    config: IggyConfig,
+    client: Mutex<Option<Arc<IggyClient>>>,
+    // Initialised once per key, without holding the map lock while connecting
+    producers: Mutex<HashMap<(String, String), ProducerCell>>,
+    health: RwLock<IggyHealth>,
+}
+
+impl std::fmt::Debug for IggyPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IggyPool")
+            .field("address", &self.config.address)
+            .field("health", &self.health())
+            .finish()
+    }
+}
+
+impl IggyPool {
+    pub fn new(config: IggyConfig) -> Self {
+        Self {
+            config,
+            client: Mutex::new(None),
+            producers: Mutex::new(HashMap::new()),
+            health: RwLock::new(IggyHealth::default()),
+        }
+    }
+
+    pub fn config(&self) -> &IggyConfig {
+        &self.config
+    }
+
+    pub fn health(&self) -> IggyHealth {
+        self.health.read().map(|h| h.clone()).unwrap_or_default()
+    }
+
+    /// Connects and logs in unless already connected.
+    pub async fn connect(&self) -> AnyhowResult<()> {
+        self.client().await.map(|_| ())
+    }
+
+    /// Sends one message, reconnecting once if the cached connection failed.
+    pub async fn send(&self, stream: &str, topic: &str, payload: &str) -> AnyhowResult<()> {
+        match self.try_send(stream, topic, payload).await {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                warn!("Iggy send failed, reconnecting: {}", e);
+                self.reset(&e.to_string()).await;
+                self.try_send(stream, topic, payload)
+                    .await
+                    .inspect_err(|e| {
+                        self.set_health(false, Some(e.to_string()));
+                    })
+            }
+        }
+    }
+
+    /// Joins `group` on a topic. Offsets are not committed automatically: the
+    /// caller stores each one once the message is processed, so a restarted
+    /// worker gets again the messages it had not finished.
+    pub async fn consumer(
+        &self,
+        group: &str,
+        stream: &str,
+        topic: &str,
+    ) -> AnyhowResult<IggyConsumer> {
+        let client = self.client().await?;
+        let mut consumer = client
+            .consumer_group(group, stream, topic)?
+            .auto_commit(AutoCommit::Disabled)
+            .create_consumer_group_if_not_exists()
+            .auto_join_consumer_group()
+            .build();
+        consumer
+            .init()
+            .await
+            .map_err(|e| anyhow!("Failed to consume {}/{}: {}", stream, topic, e))?;
+        Ok(consumer)
+    }
+
+    async fn try_send(&self, stream: &str, topic: &str, payload: &str) -> AnyhowResult<()> {
+        let producer = self.producer(stream, topic).await?;
+        let message = Message::from_str(payload)?;
+        producer
+            .send(vec![message])
+            .await
+            .map_err(|e| anyhow!("Failed to send to {}/{}: {}", stream, topic, e))
+    }
+
+    // Connects without holding the lock, so a slow or unreachable broker does
+    // not block `reset`; the first connection stored wins
+    async fn client(&self) -> AnyhowResult<Arc<IggyClient>> {
+        if let Some(client) = self.client.lock().await.as_ref() {
+            return Ok(client.clone());
+        }
+
+        match get_authenticated_iggy_client(&self.config).await {
+            Ok(connected) => {
+                let connected: Arc<IggyClient> = Arc::from(connected);
+                let client = self
+                    .client
+                    .lock()
+                    .await
+                    .get_or_insert_with(|| connected)
+                    .clone();
+                self.set_health(true, None);
+                info!("Connected to Iggy at {}", self.config.address);
+                Ok(client)
+            }
+            Err(e) => {
+                self.set_health(false, Some(e.to_string()));
+                Err(e)
+            }
+        }
+    }
+
+    async fn producer(&self, stream: &str, topic: &str) -> AnyhowResult<Arc<IggyProducer>> {
+        let key = (stream.to_string(), topic.to_string());
+        let cell = self.producers.lock().await.entry(key).or_default().clone();
+
+        let producer = cell
+            .get_or_try_init(|| async {
+                let client = self.client().await?;
+                let mut producer = client.producer(stream, topic)?.build();
+                producer.init().await?;
+                Ok::<_, anyhow::Error>(Arc::new(producer))
+            })
+            .await?;
+        Ok(producer.clone())
+    }
+
+    async fn reset(&self, error: &str) {
+        self.producers.lock().await.clear();
+        *self.client.lock().await = None;
+        self.set_health(false, Some(error.to_string()));
+    }
+
+    fn set_health(&self, connected: bool, last_error: Option<String>) {
+        if let Ok(mut health) = self.health.write() {
+            *health = IggyHealth {
+                connected,
+                last_error,
+            };
+        }
+    }
+}
@@ -1,2 +1,3 @@
 pub mod get_authenticated_iggy_client;
+pub mod iggy_pool;
 pub mod send_structured_message;
@@ -1,4 +1,5 @@
 use lazy_static::lazy_static;
+use regex::Regex;
 use std::collections::HashMap;
 
 use crate::config::{LanguagePatterns, NegationPattern};
@@ -17,7 +18,7 @@ lazy_static! {
                 NegationPattern { pattern: "ne pas ne pas", count: 2 },
                 NegationPattern { pattern: "pas ne pas", count: 2 },
             ],
-            articles: vec![" le ", " la ", " les ", " l'"],
+            articles: vec![" le ", " la ", " les ", " l'", " un ", " une ", " des "],
             polite_phrases: vec![
                 "s'il vous plaît ",
                 "s'il vous plait ",
@@ -26,6 +27,8 @@ lazy_static! {
                 "je voudrais ",
                 "je souhaite ",
             ],
+            conjunctions: vec!["et puis", "et ensuite", "et", "puis", "ensuite"],
+            follow_up_markers: vec!["aussi", "également", "pareil", "de même", "plutôt", "à la place"],
         });
 
         // English patterns
@@ -39,15 +42,52 @@ lazy_static! {
                 NegationPattern { pattern: "do not not", count: 2 },
                 NegationPattern { pattern: "never not", count: 2 },
             ],
-            articles: vec![" the "],
+            articles: vec![" the ", " a ", " an "],
             polite_phrases: vec![
                 "please ",
                 "could you ",
                 "can you ",
                 "would you ",
             ],
+            conjunctions: vec!["and then", "and", "then"],
+            follow_up_markers: vec!["also", "too", "as well", "instead", "same", "it"],
         });
 
         m
     };
+
+    // Negation patterns of each language as whole-word regexes with their
+    // count, longest first so that "ne pas" is consumed before "pas"
+    pub static ref NEGATION_REGEXES: HashMap<&'static str, Vec<(Regex, i32)>> = LANGUAGE_PATTERNS
+        .iter()
+        .map(|(language, patterns)| {
+            let mut negations = patterns.negations.clone();
+            negations.sort_by_key(|n| std::cmp::Reverse(n.pattern.len()));
+            let regexes = negations
+                .iter()
+                .map(|n| {
+                    let regex = Regex::new(&format!(r"\b{}\b", regex::escape(n.pattern)))
+                        .expect("negation patterns are valid regexes once escaped");
+                    (regex, n.count)
+                })
+                .collect();
+            (*language, regexes)
+        })
+        .collect();
+
+    // Follow-up markers of each language as case-insensitive whole words
+    pub static ref FOLLOW_UP_REGEXES: HashMap<&'static str, Vec<Regex>> = LANGUAGE_PATTERNS
+        .iter()
+        .map(|(language, patterns)| {
+            let regexes = patterns
+                .follow_up_markers
+                .iter()
+                .map(|marker| {
+                    Regex::new(&format!(r"(?i)(?:^|\s){}(?:\s|$)", regex::escape(marker)))
+                        .expect("follow-up markers are valid regexes once escaped")
+                })
+                .collect();
+            (*language, regexes)
+        })
+        .collect();
 }
@@ -1,18 +1,21 @@
 pub mod language_patterns;
 pub mod preprocess_query;
+pub mod spell_correction;
+pub mod split_intents;
 
 use lazy_static::lazy_static;
 use regex::Regex;
 
 lazy_static! {
     pub static ref EMAIL_REGEX: Regex = Regex::new(r"[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}").unwrap();
-    // Pattern for finding app names in French sentences
+    // Pattern for finding app names in French sentences, longest prefix first
+    // so that "de l'application " is tried before "de "
     pub static ref APP_PATTERNS: Vec<(&'static str, &'static str)> = vec![
-        ("de ", ""), // "analyse de gpecs"
-        ("du ", ""), // "analyse du gpecs"
-        ("pour ", ""), // "analyse pour gpecs"
-        ("sur ", ""), // "analyse sur gpecs"
         (" de l'application ", ""), // "analyse de l'application gpecs"
         (" de l'app ", ""), // "analyse de l'app gpecs"
+        ("pour ", ""), // "analyse pour gpecs"
+        ("sur ", ""), // "analyse sur gpecs"
+        ("de ", ""), // "analyse de gpecs"
+        ("du ", ""), // "analyse du gpecs"
     ];
 }
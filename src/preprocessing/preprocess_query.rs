@@ -1,17 +1,32 @@
-use super::language_patterns::LANGUAGE_PATTERNS;
+use super::language_patterns::{LANGUAGE_PATTERNS, NEGATION_REGEXES};
 use super::EMAIL_REGEX;
+use crate::config::Config;
 use crate::config::LanguagePatterns;
-use crate::config::NegationPattern;
 use crate::config::ProcessedQuery;
 use crate::filters::extract_app_name::extract_app_name;
 use std::collections::HashMap;
+use tracing::debug;
 
-pub fn preprocess_query(query: &str, language: &str) -> ProcessedQuery {
+pub fn preprocess_query(query: &str, language: &str, config: &Config) -> ProcessedQuery {
     let patterns = LANGUAGE_PATTERNS.get(language).unwrap_or_else(|| {
         LANGUAGE_PATTERNS.get("en").unwrap() // fallback to English
     });
 
-    let is_negated = count_negations(query, &patterns.negations) % 2 != 0;
+    // Fix typos before anything else so negations and articles are recognised
+    let (corrected_text, corrections) = match config.spell_corrector(language) {
+        Some(corrector) => {
+            let (corrected, corrections) = corrector.correct(query);
+            if !corrections.is_empty() {
+                debug!("Corrected query: '{}' -> '{}'", query, corrected);
+            }
+            (Some(corrected), corrections)
+        }
+        None => (None, Vec::new()),
+    };
+    let corrected_text = corrected_text.filter(|_| !corrections.is_empty());
+    let query = corrected_text.as_deref().unwrap_or(query);
+
+    let is_negated = count_negations(query, language) % 2 != 0;
     let cleaned_text = clean_text(query, patterns);
 
     let mut parameters = HashMap::new();
@@ -26,20 +41,26 @@ pub fn preprocess_query(query: &str, language: &str) -> ProcessedQuery {
         cleaned_text,
         parameters,
         is_negated,
+        corrected_text,
+        corrections,
     }
 }
 
-fn count_negations(query: &str, patterns: &[NegationPattern]) -> i32 {
-    let query = query.to_lowercase();
+fn count_negations(query: &str, language: &str) -> i32 {
+    let mut query = query.to_lowercase();
     let mut total_negations = 0;
-
-    // Sort patterns by length (longest first) to catch complete phrases first
-    let mut sorted_patterns = patterns.to_vec();
-    sorted_patterns.sort_by(|a, b| b.pattern.len().cmp(&a.pattern.len()));
-
-    for pattern in sorted_patterns {
-        if query.contains(pattern.pattern) {
-            total_negations += pattern.count;
+    let negations = NEGATION_REGEXES
+        .get(language)
+        .or_else(|| NEGATION_REGEXES.get("en"))
+        .expect("English language pack is always present");
+
+    // Consume each match so that shorter patterns ("pas") are not counted
+    // again inside longer ones ("ne pas")
+    for (regex, count) in negations {
+        let occurrences = regex.find_iter(&query).count() as i32;
+        if occurrences > 0 {
+            total_negations += occurrences * count;
+            query = regex.replace_all(&query, " ").into_owned();
         }
     }
 
@@ -83,7 +104,7 @@ mod tests {
         ];
 
         for (input, lang, should_be_negated) in test_cases {
-            let processed = preprocess_query(input, lang);
+            let processed = preprocess_query(input, lang, &Config::default());
             assert_eq!(
                 processed.is_negated, should_be_negated,
                 "Failed for '{}' ({}): expected negated={}",
@@ -101,6 +122,8 @@ mod tests {
             ("analyse de l'application testapp", Some("testapp")),
             ("analyse de l'app myapp", Some("myapp")),
             ("analyse pour app123", Some("app123")),
+            // A rejected candidate falls through to the next prefix
+            ("analyse de mes données pour gpecs", Some("gpecs")),
             // Negative cases
             ("juste une analyse", None),
             ("analyse de ", None),
@@ -138,8 +161,34 @@ mod tests {
         ];
 
         for (input, expected) in test_cases {
-            let processed = preprocess_query(input, "fr");
+            let processed = preprocess_query(input, "fr", &Config::default());
             assert_eq!(processed.cleaned_text, expected);
         }
     }
+
+    #[test]
+    fn test_spell_correction() {
+        let mut config: Config = serde_yaml::from_str(
+            r#"
+endpoints:
+  - id: "send_email"
+    text: "envoyer email"
+    description: "Envoyer un email"
+    patterns:
+      fr:
+        - "envoie le mail à {email}"
+"#,
+        )
+        .unwrap();
+        config.preprocessing.spell_correction = true;
+
+        let processed = preprocess_query("Envoie le mial à toto@gmail.com", "fr", &config);
+        assert_eq!(
+            processed.corrected_text.as_deref(),
+            Some("Envoie le mail à toto@gmail.com")
+        );
+        assert_eq!(processed.cleaned_text, "envoie mail à toto@gmail.com");
+        assert_eq!(processed.corrections.len(), 1);
+        assert_eq!(processed.corrections[0].original, "mial");
+    }
 }
@@ -0,0 +1,311 @@
+use std::collections::{HashMap, HashSet};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use tracing::debug;
+
+use super::language_patterns::LANGUAGE_PATTERNS;
+use super::APP_PATTERNS;
+use crate::config::{Config, SpellCorrection};
+
+lazy_static! {
+    static ref WORD_REGEX: Regex = Regex::new(r"[\p{L}\p{N}@._%+-]+").unwrap();
+    static ref PLACEHOLDER_REGEX: Regex = Regex::new(r"\{[^}]*\}").unwrap();
+    // Quoted values (titles, subjects...) are parameters, never corrected
+    static ref QUOTED_REGEX: Regex = Regex::new(r#""[^"]*"|“[^”]*”|«[^»]*»"#).unwrap();
+}
+
+// Words shorter than this are never corrected ("de", "à", "le"...)
+const MIN_WORD_LENGTH: usize = 3;
+
+/// Symmetric delete spelling corrector: every vocabulary word is indexed by
+/// all the strings obtained by deleting up to `max_edit_distance` characters,
+/// so a lookup only needs to generate the deletes of the misspelled word.
+#[derive(Debug, Clone)]
+pub struct SpellCorrector {
+    max_edit_distance: usize,
+    words: HashMap<String, usize>,
+    deletes: HashMap<String, Vec<String>>,
+    // Words introducing a parameter value ("à {email}", "de {app}"): the
+    // word that follows them is left as typed
+    parameter_prefixes: HashSet<String>,
+}
+
+impl SpellCorrector {
+    pub fn new<'a>(
+        vocabulary: impl IntoIterator<Item = &'a str>,
+        max_edit_distance: usize,
+    ) -> Self {
+        let mut words: HashMap<String, usize> = HashMap::new();
+        let mut parameter_prefixes: HashSet<String> = APP_PATTERNS
+            .iter()
+            .filter_map(|(prefix, _)| WORD_REGEX.find_iter(prefix).last())
+            .map(|word| word.as_str().to_string())
+            .collect();
+        for text in vocabulary {
+            let text = text.to_lowercase();
+            for placeholder in PLACEHOLDER_REGEX.find_iter(&text) {
+                if let Some(prefix) = WORD_REGEX.find_iter(&text[..placeholder.start()]).last() {
+                    parameter_prefixes.insert(prefix.as_str().to_string());
+                }
+            }
+            let text = PLACEHOLDER_REGEX.replace_all(&text, " ").into_owned();
+            for word in WORD_REGEX.find_iter(&text) {
+                let word = word.as_str();
+                if is_correctable(word) {
+                    *words.entry(word.to_string()).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let mut deletes: HashMap<String, Vec<String>> = HashMap::new();
+        for word in words.keys() {
+            for variant in generate_deletes(word, max_edit_distance) {
+                deletes.entry(variant).or_default().push(word.clone());
+            }
+        }
+
+        Self {
+            max_edit_distance,
+            words,
+            deletes,
+            parameter_prefixes,
+        }
+    }
+
+    /// Builds a corrector from the patterns of every endpoint written in
+    /// `language` plus the words of the matching language pack.
+    pub fn from_config(config: &Config, language: &str) -> Self {
+        let mut vocabulary: Vec<&str> = config
+            .endpoints
+            .iter()
+            .flat_map(|endpoint| endpoint.patterns.get(language).into_iter().flatten())
+            .map(String::as_str)
+            .collect();
+
+        if let Some(patterns) = LANGUAGE_PATTERNS.get(language) {
+            vocabulary.extend(patterns.negations.iter().map(|n| n.pattern));
+            vocabulary.extend(patterns.articles.iter().copied());
+            vocabulary.extend(patterns.polite_phrases.iter().copied());
+        }
+
+        Self::new(vocabulary, config.preprocessing.max_edit_distance)
+    }
+
+    /// Returns the closest vocabulary word, or None when `word` is already
+    /// known or nothing is close enough.
+    pub fn correct_word(&self, word: &str) -> Option<(String, usize)> {
+        if !is_correctable(word) || self.words.contains_key(word) {
+            return None;
+        }
+
+        // Short words get less tolerance, otherwise almost anything matches
+        let max_distance = if word.chars().count() <= 4 {
+            self.max_edit_distance.min(1)
+        } else {
+            self.max_edit_distance
+        };
+
+        let mut candidates = HashSet::new();
+        for variant in generate_deletes(word, max_distance) {
+            if let Some(words) = self.deletes.get(&variant) {
+                candidates.extend(words.iter());
+            }
+        }
+
+        candidates
+            .into_iter()
+            .map(|candidate| (candidate, edit_distance(word, candidate)))
+            .filter(|(_, distance)| *distance <= max_distance)
+            .min_by(|(a, da), (b, db)| {
+                da.cmp(db)
+                    .then_with(|| self.words[*b].cmp(&self.words[*a]))
+                    .then_with(|| a.cmp(b))
+            })
+            .map(|(candidate, distance)| (candidate.clone(), distance))
+    }
+
+    /// Corrects the words of `text`, keeping their casing. Parameter values
+    /// are left untouched: quoted spans, the word after a parameter prefix,
+    /// and anything that looks like an email, a number or an identifier.
+    pub fn correct(&self, text: &str) -> (String, Vec<SpellCorrection>) {
+        let quoted: Vec<(usize, usize)> = QUOTED_REGEX
+            .find_iter(text)
+            .map(|m| (m.start(), m.end()))
+            .collect();
+        let mut corrections = Vec::new();
+        let mut corrected = String::with_capacity(text.len());
+        let mut last_end = 0;
+        let mut previous: Option<String> = None;
+
+        for word in WORD_REGEX.find_iter(text) {
+            corrected.push_str(&text[last_end..word.start()]);
+            last_end = word.end();
+
+            let lowercase = word.as_str().to_lowercase();
+            let is_parameter = quoted
+                .iter()
+                .any(|(start, end)| word.start() >= *start && word.end() <= *end)
+                || previous
+                    .as_ref()
+                    .is_some_and(|previous| self.parameter_prefixes.contains(previous));
+            let replacement = if is_parameter {
+                None
+            } else {
+                self.correct_word(&lowercase)
+            };
+
+            match replacement {
+                Some((replacement, distance)) => {
+                    debug!("Spell correction: '{}' -> '{}'", word.as_str(), replacement);
+                    corrected.push_str(&match_casing(word.as_str(), &replacement));
+                    corrections.push(SpellCorrection {
+                        original: word.as_str().to_string(),
+                        corrected: replacement.clone(),
+                        distance,
+                    });
+                    previous = Some(replacement);
+                }
+                None => {
+                    corrected.push_str(word.as_str());
+                    previous = Some(lowercase);
+                }
+            }
+        }
+        corrected.push_str(&text[last_end..]);
+
+        (corrected, corrections)
+    }
+}
+
+// "Anlyse" is corrected to "Analyse", not "analyse"
+fn match_casing(original: &str, replacement: &str) -> String {
+    let mut chars = replacement.chars();
+    match (original.chars().next(), chars.next()) {
+        (Some(first), Some(replacement_first)) if first.is_uppercase() => {
+            replacement_first.to_uppercase().chain(chars).collect()
+        }
+        _ => replacement.to_string(),
+    }
+}
+
+fn is_correctable(word: &str) -> bool {
+    word.chars().count() >= MIN_WORD_LENGTH && word.chars().all(char::is_alphabetic)
+}
+
+fn generate_deletes(word: &str, max_distance: usize) -> HashSet<String> {
+    let mut deletes = HashSet::new();
+    deletes.insert(word.to_string());
+
+    let mut frontier = vec![word.to_string()];
+    for _ in 0..max_distance {
+        let mut next = Vec::new();
+        for current in &frontier {
+            let chars: Vec<char> = current.chars().collect();
+            for i in 0..chars.len() {
+                let variant: String = chars[..i].iter().chain(&chars[i + 1..]).collect();
+                if deletes.insert(variant.clone()) {
+                    next.push(variant);
+                }
+            }
+        }
+        frontier = next;
+    }
+
+    deletes
+}
+
+// Optimal string alignment distance: Levenshtein plus adjacent transpositions,
+// so "mial" is one edit away from "mail"
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for j in 0..=b.len() {
+        d[0][j] = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corrector() -> SpellCorrector {
+        SpellCorrector::new(
+            vec![
+                "analyse de {app}",
+                "lancer analyse de {app}",
+                "envoi le mail à {email}",
+                "envoie le document à {email}",
+            ],
+            2,
+        )
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("mail", "mail"), 0);
+        assert_eq!(edit_distance("mial", "mail"), 1);
+        assert_eq!(edit_distance("anlyse", "analyse"), 1);
+        assert_eq!(edit_distance("envoi", "envoie"), 1);
+        assert_eq!(edit_distance("abc", "xyz"), 3);
+    }
+
+    #[test]
+    fn test_corrects_typos() {
+        let corrector = corrector();
+        let test_cases = vec![
+            ("anlyse de gpecs", "analyse de gpecs"),
+            ("envoi le mial", "envoi le mail"),
+            ("lancr analyse de divess", "lancer analyse de divess"),
+        ];
+
+        for (input, expected) in test_cases {
+            let (corrected, corrections) = corrector.correct(input);
+            assert_eq!(corrected, expected, "Failed for '{}'", input);
+            assert_eq!(corrections.len(), 1, "Failed for '{}'", input);
+        }
+    }
+
+    #[test]
+    fn test_leaves_parameters_untouched() {
+        let corrector = corrector();
+        for input in [
+            "envoie le document à toto@gmail.com",
+            // App names after a parameter prefix, even when close to a known word
+            "analyse de Lancr",
+            "analyse de l'application Analyze",
+            // Quoted titles and subjects
+            "envoie le document \"Rapport anlyse\"",
+        ] {
+            let (corrected, corrections) = corrector.correct(input);
+            assert_eq!(corrected, input);
+            assert!(corrections.is_empty(), "Failed for '{}'", input);
+        }
+    }
+
+    #[test]
+    fn test_keeps_casing() {
+        let (corrected, corrections) = corrector().correct("Anlyse de GPECS");
+        assert_eq!(corrected, "Analyse de GPECS");
+        assert_eq!(corrections[0].original, "Anlyse");
+    }
+}
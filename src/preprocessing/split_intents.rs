@@ -0,0 +1,169 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+
+use super::language_patterns::LANGUAGE_PATTERNS;
+use crate::config::Config;
+
+lazy_static! {
+    // Conjunctions of each language pack, plus punctuation followed by a
+    // space or the end of the query so that emails and domains stay intact
+    static ref SEPARATOR_REGEXES: HashMap<&'static str, Regex> = LANGUAGE_PATTERNS
+        .iter()
+        .map(|(language, patterns)| {
+            let mut conjunctions = patterns.conjunctions.clone();
+            conjunctions.sort_by_key(|c| std::cmp::Reverse(c.len()));
+            let conjunctions = conjunctions
+                .iter()
+                .map(|c| regex::escape(c))
+                .collect::<Vec<_>>()
+                .join("|");
+            let regex = Regex::new(&format!(
+                r"(?i)[;,.!?](?:\s+(?:(?:{0})\s+)?|$)|\s+(?:{0})\s+",
+                conjunctions
+            ))
+            .expect("conjunctions are valid regexes once escaped");
+            (*language, regex)
+        })
+        .collect();
+}
+
+// Fragments shorter than this ("et divess", "and bob") complete the previous
+// clause instead of becoming an intent of their own
+const MIN_CLAUSE_WORDS: usize = 2;
+
+/// First words of the endpoint patterns in `language` ("envoyer", "run"),
+/// lowercased. A clause has to start with one of them to be an intent of its own.
+pub fn action_words(config: &Config, language: &str) -> HashSet<String> {
+    config
+        .endpoints
+        .iter()
+        .filter_map(|endpoint| endpoint.patterns.get(language))
+        .flatten()
+        .filter_map(|pattern| pattern.split_whitespace().next())
+        .filter(|word| !word.starts_with('{'))
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// Splits a compound query into clauses, in order, one per intent.
+/// A query without separators is returned as a single clause, and so is a
+/// separator inside a parameter value ("à Tom Dupont et Jerry Martin"): the
+/// text after it does not start with one of the `action_words`.
+pub fn split_intents(query: &str, language: &str, action_words: &HashSet<String>) -> Vec<String> {
+    let regex = SEPARATOR_REGEXES
+        .get(language)
+        .or_else(|| SEPARATOR_REGEXES.get("en"))
+        .expect("English language pack is always present");
+
+    let mut clauses: Vec<String> = Vec::new();
+    let mut start = 0;
+    let mut separator = "";
+    let pieces = regex
+        .find_iter(query)
+        .map(|m| (m.start(), m.end()))
+        .chain(std::iter::once((query.len(), query.len())));
+
+    for (end, next_start) in pieces {
+        let piece = query[start..end].trim();
+        if !piece.is_empty() {
+            let short = |text: &str| text.split_whitespace().count() < MIN_CLAUSE_WORDS;
+            let starts_action = piece
+                .split_whitespace()
+                .next()
+                .is_some_and(|word| action_words.contains(&word.to_lowercase()));
+            match clauses.last_mut() {
+                Some(last) if short(piece) || short(last) || !starts_action => {
+                    last.push_str(separator);
+                    last.push_str(piece);
+                }
+                _ => clauses.push(piece.to_string()),
+            }
+        }
+        separator = &query[end..next_start];
+        start = next_start;
+    }
+
+    if clauses.is_empty() {
+        clauses.push(query.trim().to_string());
+    }
+    clauses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn actions() -> HashSet<String> {
+        ["analyse", "lance", "envoie", "envoyer", "run", "send"]
+            .iter()
+            .map(|word| word.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_split_compound_queries() {
+        let test_cases = vec![
+            (
+                "Analyse gpecs et envoie le rapport à toto@gmail.com",
+                "fr",
+                vec!["Analyse gpecs", "envoie le rapport à toto@gmail.com"],
+            ),
+            (
+                "lance l'analyse de divess, puis envoie un mail à bob@x.com.",
+                "fr",
+                vec!["lance l'analyse de divess", "envoie un mail à bob@x.com"],
+            ),
+            (
+                "run analysis of gpecs and then send an email to bob@x.com",
+                "en",
+                vec!["run analysis of gpecs", "send an email to bob@x.com"],
+            ),
+        ];
+
+        for (query, language, expected) in test_cases {
+            assert_eq!(
+                split_intents(query, language, &actions()),
+                expected,
+                "Failed for '{}'",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn test_short_fragments_stay_together() {
+        assert_eq!(
+            split_intents("analyse de gpecs et divess", "fr", &actions()),
+            vec!["analyse de gpecs et divess"]
+        );
+        assert_eq!(
+            split_intents("oui, analyse de gpecs", "fr", &actions()),
+            vec!["oui, analyse de gpecs"]
+        );
+        assert_eq!(
+            split_intents("envoie le mail à toto@gmail.com", "fr", &actions()),
+            vec!["envoie le mail à toto@gmail.com"]
+        );
+    }
+
+    #[test]
+    fn test_parameter_values_stay_together() {
+        assert_eq!(
+            split_intents(
+                "envoyer un mail à Tom Dupont et Jerry Martin",
+                "fr",
+                &actions()
+            ),
+            vec!["envoyer un mail à Tom Dupont et Jerry Martin"]
+        );
+        assert_eq!(
+            split_intents(
+                "send an email to Tom and Jerry, then run analysis of gpecs",
+                "en",
+                &actions()
+            ),
+            vec!["send an email to Tom and Jerry", "run analysis of gpecs"]
+        );
+    }
+}
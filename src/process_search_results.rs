@@ -1,10 +1,14 @@
-use crate::messaging::send_structured_message::send_structured_message;
-use crate::{
-    config::SearchResult, messaging::get_authenticated_iggy_client::get_authenticated_iggy_client,
-};
+use crate::actions::{dispatch_action, Action, DispatchOutcome};
+use crate::config::{Config, SearchResult};
+use crate::preprocessing::preprocess_query::preprocess_query;
 use anyhow::{anyhow, Result as AnyhowResult};
 
-pub async fn process_search_results(results: Vec<SearchResult>) -> AnyhowResult<()> {
+pub async fn process_search_results(
+    results: Vec<SearchResult>,
+    query: &str,
+    language: &str,
+    config: &Config,
+) -> AnyhowResult<()> {
     // Only proceed with the best match (first result)
     let best_match = if let Some(result) = results.first() {
         result
@@ -13,31 +17,44 @@ pub async fn process_search_results(results: Vec<SearchResult>) -> AnyhowResult<
     };
 
     println!(
-        "Processing best match with similarity: {}",
-        best_match.similarity
+        "Processing best match with similarity: {} (confidence: {:.3})",
+        best_match.similarity, best_match.confidence
     );
 
-    let client = get_authenticated_iggy_client().await?;
-    let message_params: Vec<String> = best_match.parameters.values().cloned().collect();
-    if let Err(e) = send_structured_message(
-        &client,
-        "gibro",
-        "notification",
-        &best_match.endpoint_id,
-        message_params,
-    )
-    .await
-    {
-        eprintln!(
-            "Failed to send message for endpoint {}: {}",
-            best_match.endpoint_id, e
-        );
-        return Err(anyhow!("Message sending failed: {}", e));
+    let action = Action {
+        endpoint_id: best_match.endpoint_id.clone(),
+        parameters: best_match.parameters.clone(),
+        similarity: best_match.similarity,
+        negated: preprocess_query(query, language, config).is_negated,
+        query: Some(query.to_string()),
+        language: Some(language.to_string()),
+        session_id: None,
+        request_id: None,
+    };
+    match dispatch_action(&action, config).await {
+        Ok(DispatchOutcome::Dispatched { endpoint_id, .. })
+            if endpoint_id != action.endpoint_id =>
+        {
+            println!("Negated query, dispatched inverse endpoint {}", endpoint_id);
+        }
+        Ok(DispatchOutcome::Suppressed) => {
+            println!(
+                "Negated query, action for endpoint {} suppressed",
+                best_match.endpoint_id
+            );
+        }
+        Ok(DispatchOutcome::Queued { error, .. }) => {
+            println!("Dispatch failed, queued in the outbox for retry: {}", error);
+        }
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!(
+                "Failed to dispatch action for endpoint {}: {}",
+                best_match.endpoint_id, e
+            );
+            return Err(anyhow!("Action dispatch failed: {}", e));
+        }
     }
-    println!(
-        "Sent notification for endpoint: {} with similarity: {}",
-        best_match.endpoint_id, best_match.similarity
-    );
 
     println!("Completed processing best match");
     Ok(())
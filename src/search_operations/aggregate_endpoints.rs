@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use crate::config::{AggregationStrategy, SearchConfig, SearchResult};
+
+/// Groups pattern matches by endpoint and scores each endpoint from all of
+/// its matching patterns. The best pattern of each endpoint is kept as the
+/// representative match (for parameters), with the aggregated similarity.
+/// Results are sorted by decreasing similarity.
+pub fn aggregate_by_endpoint(
+    matches: Vec<SearchResult>,
+    strategy: AggregationStrategy,
+    search_config: &SearchConfig,
+) -> Vec<SearchResult> {
+    let mut by_endpoint: HashMap<String, Vec<SearchResult>> = HashMap::new();
+    for match_data in matches {
+        by_endpoint
+            .entry(match_data.endpoint_id.clone())
+            .or_default()
+            .push(match_data);
+    }
+
+    let mut aggregated: Vec<SearchResult> = by_endpoint
+        .into_values()
+        .map(|mut patterns| {
+            patterns.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+            let similarities: Vec<f32> = patterns.iter().map(|p| p.similarity).collect();
+            let mut best = patterns.swap_remove(0);
+            best.similarity = aggregate(&similarities, strategy, search_config);
+            best
+        })
+        .collect();
+
+    aggregated.sort_by(|a, b| {
+        b.similarity
+            .total_cmp(&a.similarity)
+            .then_with(|| a.endpoint_id.cmp(&b.endpoint_id))
+    });
+    aggregated
+}
+
+// `similarities` is sorted in decreasing order and never empty
+fn aggregate(
+    similarities: &[f32],
+    strategy: AggregationStrategy,
+    search_config: &SearchConfig,
+) -> f32 {
+    match strategy {
+        AggregationStrategy::Max => similarities[0],
+        AggregationStrategy::MeanTopK => {
+            let top_k =
+                &similarities[..search_config.aggregation_top_k.clamp(1, similarities.len())];
+            top_k.iter().sum::<f32>() / top_k.len() as f32
+        }
+        AggregationStrategy::Softmax => {
+            // Weighted mean where better matching patterns weigh more; a low
+            // temperature approaches max, a high one approaches the mean
+            let temperature = search_config.softmax_temperature.max(f32::EPSILON);
+            let weights: Vec<f32> = similarities
+                .iter()
+                .map(|s| ((s - similarities[0]) / temperature).exp())
+                .collect();
+            let total: f32 = weights.iter().sum();
+            similarities
+                .iter()
+                .zip(&weights)
+                .map(|(s, w)| s * w)
+                .sum::<f32>()
+                / total
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_operations::test_result as result;
+
+    fn matches() -> Vec<SearchResult> {
+        vec![
+            result("send_email", "envoie le mail à {email}", 0.90),
+            result("send_email", "envoie le document à {email}", 0.50),
+            result("send_email", "envoi le rapport à {email}", 0.40),
+            result("analyze_specific_repository", "analyse de {app}", 0.80),
+            result("analyze_specific_repository", "analyser {app}", 0.78),
+        ]
+    }
+
+    #[test]
+    fn test_one_result_per_endpoint() {
+        let results = aggregate_by_endpoint(
+            matches(),
+            AggregationStrategy::Max,
+            &SearchConfig::default(),
+        );
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].endpoint_id, "send_email");
+        assert_eq!(results[0].pattern, "envoie le mail à {email}");
+        assert!((results[0].similarity - 0.90).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_mean_top_k_favours_consistent_endpoints() {
+        let config = SearchConfig {
+            aggregation_top_k: 2,
+            ..Default::default()
+        };
+        let results = aggregate_by_endpoint(matches(), AggregationStrategy::MeanTopK, &config);
+
+        assert_eq!(results[0].endpoint_id, "analyze_specific_repository");
+        assert!((results[0].similarity - 0.79).abs() < 1e-6);
+        assert!((results[1].similarity - 0.70).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_softmax_between_mean_and_max() {
+        let results = aggregate_by_endpoint(
+            matches(),
+            AggregationStrategy::Softmax,
+            &SearchConfig::default(),
+        );
+
+        let send_email = results
+            .iter()
+            .find(|r| r.endpoint_id == "send_email")
+            .unwrap();
+        assert!(send_email.similarity < 0.90);
+        assert!(send_email.similarity > (0.90 + 0.50 + 0.40) / 3.0);
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use crate::config::{FusionMethod, SearchConfig, SearchResult};
+
+/// Combines vector similarities (already in `results`) with full-text scores
+/// keyed by (endpoint_id, pattern). The fused score, scaled to [0, 1],
+/// replaces `similarity` so that ordering and reporting stay unchanged.
+pub fn fuse_scores(
+    results: &mut [SearchResult],
+    lexical_scores: &HashMap<(String, String), f32>,
+    fusion: FusionMethod,
+    search_config: &SearchConfig,
+) {
+    match fusion {
+        FusionMethod::WeightedSum => {
+            let max_lexical = lexical_scores.values().copied().fold(0.0_f32, f32::max);
+            let vector_weight = search_config.vector_weight.clamp(0.0, 1.0);
+            for result in results.iter_mut() {
+                let lexical = lexical_scores
+                    .get(&(result.endpoint_id.clone(), result.pattern.clone()))
+                    .filter(|_| max_lexical > 0.0)
+                    .map_or(0.0, |score| score / max_lexical);
+                result.similarity =
+                    vector_weight * result.similarity + (1.0 - vector_weight) * lexical;
+            }
+        }
+        FusionMethod::ReciprocalRank => {
+            let k = search_config.rrf_k.max(0.0);
+            let vector_ranks = ranks(
+                results
+                    .iter()
+                    .map(|r| ((r.endpoint_id.clone(), r.pattern.clone()), r.similarity)),
+            );
+            let lexical_ranks = ranks(
+                lexical_scores
+                    .iter()
+                    .map(|(key, score)| (key.clone(), *score)),
+            );
+
+            // Best possible score: ranked first by both retrievers. The result is
+            // rank based: first for one retriever only already scores about 0.5,
+            // so thresholds tuned for weighted_sum do not carry over
+            let max_score = 2.0 / (k + 1.0);
+            for result in results.iter_mut() {
+                let key = (result.endpoint_id.clone(), result.pattern.clone());
+                let score: f32 = [vector_ranks.get(&key), lexical_ranks.get(&key)]
+                    .into_iter()
+                    .flatten()
+                    .map(|rank| 1.0 / (k + *rank as f32))
+                    .sum();
+                result.similarity = score / max_score;
+            }
+        }
+    }
+}
+
+// 1-based rank of each key, highest score first
+fn ranks(
+    scores: impl Iterator<Item = ((String, String), f32)>,
+) -> HashMap<(String, String), usize> {
+    let mut scores: Vec<_> = scores.collect();
+    scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    scores
+        .into_iter()
+        .enumerate()
+        .map(|(idx, (key, _))| (key, idx + 1))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_operations::test_result as result;
+
+    fn key(endpoint_id: &str, pattern: &str) -> (String, String) {
+        (endpoint_id.to_string(), pattern.to_string())
+    }
+
+    #[test]
+    fn test_weighted_sum_breaks_vector_tie() {
+        // Same structure, only the verb differs: the vector scores are close
+        // but the lexical match on "supprime" decides
+        let mut results = vec![
+            result("send_document", "envoie le document", 0.91),
+            result("delete_document", "supprime le document", 0.90),
+        ];
+        let lexical = HashMap::from([
+            (key("send_document", "envoie le document"), 0.4),
+            (key("delete_document", "supprime le document"), 1.6),
+        ]);
+        let config = SearchConfig {
+            vector_weight: 0.5,
+            ..Default::default()
+        };
+
+        fuse_scores(&mut results, &lexical, FusionMethod::WeightedSum, &config);
+
+        assert!(results[1].similarity > results[0].similarity);
+        assert!((results[1].similarity - 0.95).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_reciprocal_rank_fusion() {
+        let mut results = vec![
+            result("send_document", "envoie le document", 0.91),
+            result("delete_document", "supprime le document", 0.90),
+            result("send_email", "envoie un mail", 0.80),
+        ];
+        let lexical = HashMap::from([
+            (key("delete_document", "supprime le document"), 1.6),
+            (key("send_document", "envoie le document"), 0.4),
+        ]);
+        let config = SearchConfig {
+            rrf_k: 60.0,
+            ..Default::default()
+        };
+
+        fuse_scores(
+            &mut results,
+            &lexical,
+            FusionMethod::ReciprocalRank,
+            &config,
+        );
+
+        // Ranked 1st + 2nd for both documents, so they tie; the email pattern
+        // only appears in the vector results
+        assert!((results[0].similarity - results[1].similarity).abs() < 1e-6);
+        assert!(results[2].similarity < results[0].similarity);
+        assert!(results.iter().all(|r| r.similarity <= 1.0));
+    }
+}
@@ -1,5 +1,31 @@
+mod aggregate_endpoints;
+mod hybrid_fusion;
+mod negative_examples;
 pub mod parameter_analysis;
 mod process_search_batch;
+mod rerank;
 mod search_similar;
 
 pub use search_similar::search_similar;
+
+#[cfg(test)]
+use crate::config::{ParameterAnalysis, SearchResult};
+
+/// Pattern match fixture shared by the search tests.
+#[cfg(test)]
+pub(crate) fn test_result(endpoint_id: &str, pattern: &str, similarity: f32) -> SearchResult {
+    SearchResult {
+        endpoint_id: endpoint_id.to_string(),
+        pattern: pattern.to_string(),
+        language: "fr".to_string(),
+        similarity,
+        confidence: similarity,
+        is_negative: false,
+        parameters: std::collections::HashMap::new(),
+        parameter_analysis: ParameterAnalysis {
+            missing_required: vec![],
+            missing_optional: vec![],
+            found: std::collections::HashMap::new(),
+        },
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use tracing::debug;
+
+use crate::config::{NegativeMode, NegativesConfig, SearchResult, Suppression};
+
+/// Removes negative example rows from `matches` and penalises (or excludes)
+/// every endpoint whose closest row is one of its negative examples.
+pub fn apply_negative_examples(
+    matches: Vec<SearchResult>,
+    negatives_config: &NegativesConfig,
+) -> (Vec<SearchResult>, Vec<Suppression>) {
+    let (negatives, mut positives): (Vec<_>, Vec<_>) =
+        matches.into_iter().partition(|m| m.is_negative);
+
+    // Closest negative example per endpoint
+    let mut closest_negatives: HashMap<&str, &SearchResult> = HashMap::new();
+    for negative in &negatives {
+        closest_negatives
+            .entry(negative.endpoint_id.as_str())
+            .and_modify(|existing| {
+                if negative.similarity > existing.similarity {
+                    *existing = negative;
+                }
+            })
+            .or_insert(negative);
+    }
+
+    let mut suppressed = Vec::new();
+    for (endpoint_id, negative) in closest_negatives {
+        let best_positive = positives
+            .iter()
+            .filter(|p| p.endpoint_id == endpoint_id)
+            .map(|p| p.similarity)
+            .fold(f32::NEG_INFINITY, f32::max);
+        if negative.similarity < best_positive {
+            continue;
+        }
+
+        let excluded = negatives_config.mode == NegativeMode::Exclude;
+        let reason = if excluded {
+            format!(
+                "closest to negative example '{}' ({:.3}), endpoint excluded",
+                negative.pattern, negative.similarity
+            )
+        } else {
+            format!(
+                "closest to negative example '{}' ({:.3}), similarity reduced by {}",
+                negative.pattern, negative.similarity, negatives_config.penalty
+            )
+        };
+        debug!("Suppressing endpoint '{}': {}", endpoint_id, reason);
+
+        if excluded {
+            positives.retain(|p| p.endpoint_id != endpoint_id);
+        } else {
+            for positive in positives
+                .iter_mut()
+                .filter(|p| p.endpoint_id == endpoint_id)
+            {
+                positive.similarity -= negatives_config.penalty;
+            }
+        }
+
+        suppressed.push(Suppression {
+            endpoint_id: endpoint_id.to_string(),
+            negative_pattern: negative.pattern.clone(),
+            similarity: negative.similarity,
+            excluded,
+            reason,
+        });
+    }
+
+    (positives, suppressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_operations::test_result;
+
+    fn result(
+        endpoint_id: &str,
+        pattern: &str,
+        similarity: f32,
+        is_negative: bool,
+    ) -> SearchResult {
+        SearchResult {
+            is_negative,
+            ..test_result(endpoint_id, pattern, similarity)
+        }
+    }
+
+    fn matches() -> Vec<SearchResult> {
+        vec![
+            result("send_email", "envoyer un mail à {email}", 0.80, false),
+            result("send_email", "lire mes mails", 0.85, true),
+            result(
+                "analyze_specific_repository",
+                "analyse de {app}",
+                0.60,
+                false,
+            ),
+            result(
+                "analyze_specific_repository",
+                "supprimer l'analyse",
+                0.40,
+                true,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_penalize_closest_negative() {
+        let (results, suppressed) = apply_negative_examples(matches(), &NegativesConfig::default());
+
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| !r.is_negative));
+        let send_email = results
+            .iter()
+            .find(|r| r.endpoint_id == "send_email")
+            .unwrap();
+        assert!((send_email.similarity - 0.60).abs() < 1e-6);
+
+        // The analysis negative example is further than its positive pattern
+        assert_eq!(suppressed.len(), 1);
+        assert_eq!(suppressed[0].endpoint_id, "send_email");
+        assert!(!suppressed[0].excluded);
+    }
+
+    #[test]
+    fn test_exclude_closest_negative() {
+        let config = NegativesConfig {
+            mode: NegativeMode::Exclude,
+            ..Default::default()
+        };
+        let (results, suppressed) = apply_negative_examples(matches(), &config);
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].endpoint_id, "analyze_specific_repository");
+        assert!(suppressed[0].excluded);
+        assert!(suppressed[0].reason.contains("lire mes mails"));
+    }
+}
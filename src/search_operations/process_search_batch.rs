@@ -2,13 +2,14 @@ use anyhow::Result as AnyhowResult;
 use arrow_array::{Array, RecordBatch};
 
 use crate::{
-    config::{Config, ProcessedQuery, SearchResult},
+    config::{Config, LanguageMode, ProcessedQuery, SearchResult},
     filters::extract_parameters::extract_parameters,
 };
 
 pub async fn process_search_batch(
     batch: RecordBatch,
     processed: &ProcessedQuery,
+    language: &str,
     config: &Config,
 ) -> AnyhowResult<(Vec<SearchResult>, f32)> {
     let mut results = Vec::new();
@@ -21,6 +22,12 @@ pub async fn process_search_batch(
     let endpoint_id_array = batch
         .column_by_name("endpoint_id")
         .ok_or_else(|| anyhow::anyhow!("Missing endpoint_id column"))?;
+    let language_array = batch
+        .column_by_name("language")
+        .ok_or_else(|| anyhow::anyhow!("Missing language column"))?;
+    let negative_array = batch
+        .column_by_name("is_negative")
+        .ok_or_else(|| anyhow::anyhow!("Missing is_negative column"))?;
     let distance_array = batch
         .column_by_name("_distance") // Changed from "distance" to "_distance"
         .ok_or_else(|| anyhow::anyhow!("Missing _distance column"))?;
@@ -38,6 +45,18 @@ pub async fn process_search_batch(
             .ok_or_else(|| anyhow::anyhow!("Failed to get endpoint_id as string"))?
             .value(row_idx);
 
+        let pattern_language = language_array
+            .as_any()
+            .downcast_ref::<arrow::array::StringArray>()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get language as string"))?
+            .value(row_idx);
+
+        let is_negative = negative_array
+            .as_any()
+            .downcast_ref::<arrow::array::BooleanArray>()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get is_negative as boolean"))?
+            .value(row_idx);
+
         let distance = distance_array
             .as_any()
             .downcast_ref::<arrow::array::Float32Array>()
@@ -54,9 +73,7 @@ pub async fn process_search_batch(
         if !parameters.is_empty() {
             let pattern_params = extract_parameters(&processed.cleaned_text, pattern)?;
             for (key, value) in pattern_params {
-                if !parameters.contains_key(&key) {
-                    parameters.insert(key, value);
-                }
+                parameters.entry(key).or_insert(value);
             }
         } else {
             parameters = extract_parameters(&processed.cleaned_text, pattern)?;
@@ -64,13 +81,19 @@ pub async fn process_search_batch(
 
         let parameter_analysis = endpoint.analyze_parameters(&parameters);
 
-        let similarity = 1.0 - distance;
+        let mut similarity = 1.0 - distance;
+        if config.search.language_mode == LanguageMode::Boost && pattern_language == language {
+            similarity = (similarity + config.search.language_boost).min(1.0);
+        }
         best_similarity = best_similarity.max(similarity);
 
         results.push(SearchResult {
             endpoint_id: endpoint_id.to_string(),
             pattern: pattern.to_string(),
+            language: pattern_language.to_string(),
+            is_negative,
             similarity,
+            confidence: similarity,
             parameters,
             parameter_analysis,
         });
@@ -0,0 +1,46 @@
+use anyhow::Result as AnyhowResult;
+use tracing::debug;
+
+use crate::candle::cross_encoder::get_cross_encoder;
+use crate::config::{RerankConfig, SearchResult};
+
+/// Scores the `top_k` best candidates with the cross-encoder, in the order of
+/// `results`. Once scored, candidates beyond `top_k` are dropped; on error
+/// `results` keeps every candidate.
+pub async fn cross_encoder_scores(
+    results: &mut Vec<SearchResult>,
+    query: &str,
+    rerank_config: &RerankConfig,
+) -> AnyhowResult<Vec<f32>> {
+    results.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+
+    // Model loading and inference are CPU bound, keep them off the async workers
+    let query = query.to_string();
+    let model_path = rerank_config.model_path.clone();
+    let patterns: Vec<String> = results
+        .iter()
+        .take(rerank_config.top_k)
+        .map(|r| r.pattern.clone())
+        .collect();
+    let scores = tokio::task::spawn_blocking(move || {
+        let cross_encoder = get_cross_encoder(&model_path)?;
+        let patterns: Vec<&str> = patterns.iter().map(String::as_str).collect();
+        cross_encoder.score(&query, &patterns)
+    })
+    .await??;
+
+    results.truncate(rerank_config.top_k);
+    Ok(scores)
+}
+
+/// Blends the cross-encoder scores into the similarity of `results`.
+pub fn blend_scores(results: &mut [SearchResult], scores: Vec<f32>, weight: f32) {
+    let weight = weight.clamp(0.0, 1.0);
+    for (result, score) in results.iter_mut().zip(scores) {
+        debug!(
+            "Re-ranked '{}' ({}): similarity {} -> cross-encoder {}",
+            result.pattern, result.endpoint_id, result.similarity, score
+        );
+        result.similarity = weight * score + (1.0 - weight) * result.similarity;
+    }
+}
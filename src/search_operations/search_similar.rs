@@ -1,12 +1,22 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use anyhow::{Context, Result as AnyhowResult};
+use arrow_array::{Array, RecordBatch};
+use lancedb::index::scalar::FullTextSearchQuery;
 use lancedb::query::{ExecutableQuery, QueryBase};
 use lancedb::{DistanceType, Table};
+use tracing::{debug, warn};
 
+use super::aggregate_endpoints::aggregate_by_endpoint;
+use super::hybrid_fusion::fuse_scores;
+use super::negative_examples::apply_negative_examples;
 use super::process_search_batch::process_search_batch;
+use super::rerank::{blend_scores, cross_encoder_scores};
 use crate::candle::get_embeddings::get_embeddings;
-use crate::config::{Config, SearchResult};
+use crate::config::{
+    Config, LanguageMode, ProcessedQuery, SearchMode, SearchOptions, SearchOutcome, SearchResult,
+    Suppression,
+};
 use crate::preprocessing::preprocess_query::preprocess_query;
 use futures::StreamExt;
 
@@ -16,44 +26,348 @@ pub async fn search_similar(
     language: &str,
     limit: usize,
     config: &Config,
-) -> AnyhowResult<(Vec<SearchResult>, f32)> {
-    let processed = preprocess_query(query, language);
+    options: &SearchOptions,
+) -> AnyhowResult<SearchOutcome> {
+    let processed = preprocess_query(query, language, config);
+    if let Some(corrected) = &processed.corrected_text {
+        println!("\nCorrected query: '{}'", corrected);
+    }
     println!("\nProcessed query: '{}'", processed.cleaned_text);
     let query_embedding = get_embeddings(&processed.cleaned_text).await?;
     println!("Generated query embedding, starting vector search...");
-    let mut results = patterns_table
-        .vector_search(query_embedding)
-        .context("Failed to create vector search")?
-        .distance_type(DistanceType::Cosine)
-        .limit(limit)
-        .execute()
+
+    let rerank = options.rerank.unwrap_or(config.rerank.enabled);
+    let aggregation = options.aggregation.unwrap_or(config.search.aggregation);
+
+    // The ambiguity check needs the runner-up even when one match is asked for
+    let margin = config.search.ambiguity_margin;
+    let wanted = if margin > 0.0 { limit.max(2) } else { limit };
+
+    // Several patterns usually belong to the same endpoint, so fetch more
+    // pattern rows than the number of endpoints asked for. The cross-encoder
+    // also needs enough candidates to choose from.
+    let mut fetch_limit = wanted * config.search.overfetch_factor.max(1);
+    if rerank {
+        fetch_limit = fetch_limit.max(config.rerank.top_k);
+    }
+
+    let mut suppressed;
+    let mut best_similarity;
+    let mut endpoint_matches;
+    loop {
+        let (mut candidates, rows_fetched) = retrieve_candidates(
+            patterns_table,
+            &processed,
+            &query_embedding,
+            language,
+            fetch_limit,
+            config,
+            options,
+        )
         .await?;
 
+        let rerank_scores = if rerank {
+            match cross_encoder_scores(&mut candidates, &processed.cleaned_text, &config.rerank)
+                .await
+            {
+                Ok(scores) => Some(scores),
+                Err(e) => {
+                    warn!(
+                        "Cross-encoder re-ranking failed, keeping bi-encoder scores: {}",
+                        e
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let (mut candidates, negatives_suppressed) =
+            score_candidates(candidates, rerank_scores, config);
+        suppressed = negatives_suppressed;
+
+        best_similarity = candidates
+            .iter()
+            .map(|m| m.similarity)
+            .fold(0.0_f32, f32::max);
+
+        candidates.retain(|m| m.similarity >= config.threshold_for(&m.endpoint_id));
+        endpoint_matches = aggregate_by_endpoint(candidates, aggregation, &config.search);
+
+        // Stop once enough distinct endpoints were found or the table is exhausted.
+        // Re-ranking only keeps rerank.top_k candidates, so fetching more would not help.
+        if endpoint_matches.len() >= wanted || rows_fetched < fetch_limit || rerank {
+            break;
+        }
+        fetch_limit *= 2;
+        debug!(
+            "Only {} distinct endpoints, fetching {} patterns",
+            endpoint_matches.len(),
+            fetch_limit
+        );
+    }
+
+    if let [best, runner_up, ..] = endpoint_matches.as_slice() {
+        if best.similarity - runner_up.similarity < margin {
+            debug!(
+                "Ambiguous match: {} ({:.3}) and {} ({:.3}) are closer than {}",
+                best.endpoint_id,
+                best.similarity,
+                runner_up.endpoint_id,
+                runner_up.similarity,
+                margin
+            );
+            endpoint_matches.clear();
+        }
+    }
+    endpoint_matches.truncate(limit);
+
+    Ok(SearchOutcome {
+        results: endpoint_matches,
+        best_similarity,
+        suppressed,
+        processed,
+    })
+}
+
+// Blends the cross-encoder scores into the candidates, then applies the
+// negative examples so their penalty still counts with a full re-rank weight
+fn score_candidates(
+    mut candidates: Vec<SearchResult>,
+    rerank_scores: Option<Vec<f32>>,
+    config: &Config,
+) -> (Vec<SearchResult>, Vec<Suppression>) {
+    if let Some(scores) = rerank_scores {
+        blend_scores(&mut candidates, scores, config.rerank.weight);
+    }
+    apply_negative_examples(candidates, &config.negatives)
+}
+
+// Pattern rows closest to the query (vector or hybrid), with the number of
+// rows returned by the vector search
+async fn retrieve_candidates(
+    patterns_table: &Table,
+    processed: &ProcessedQuery,
+    query_embedding: &[f32],
+    language: &str,
+    fetch_limit: usize,
+    config: &Config,
+    options: &SearchOptions,
+) -> AnyhowResult<(Vec<SearchResult>, usize)> {
+    let language_filter = (config.search.language_mode == LanguageMode::Filter
+        && !language.is_empty())
+    .then(|| format!("language = '{}'", language.replace('\'', "''")));
+
+    let mut batches = vector_search(
+        patterns_table,
+        query_embedding,
+        fetch_limit,
+        language_filter.as_deref(),
+    )
+    .await?;
+
+    let mut language_filter = language_filter;
+    if language_filter.is_some()
+        && config.search.cross_lingual_fallback
+        && batches.iter().all(|rb| rb.num_rows() == 0)
+    {
+        println!(
+            "No '{}' pattern matched, falling back to cross-lingual search...",
+            language
+        );
+        language_filter = None;
+        batches = vector_search(patterns_table, query_embedding, fetch_limit, None).await?;
+    }
+
+    let rows_fetched = batches.iter().map(|rb| rb.num_rows()).sum();
     let mut initial_matches = Vec::new();
-    let mut best_similarity: f32 = 0.0;
 
-    while let Some(Ok(rb)) = results.next().await {
-        let (new_matches, similarity) = process_search_batch(rb, &processed, config).await?;
-        best_similarity = best_similarity.max(similarity);
+    for rb in batches {
+        let (new_matches, _similarity) =
+            process_search_batch(rb, processed, language, config).await?;
         initial_matches.extend(new_matches);
     }
+    if options.mode.unwrap_or(config.search.mode) == SearchMode::Hybrid {
+        let fusion = options.fusion.unwrap_or(config.search.fusion);
+        match full_text_search(
+            patterns_table,
+            &processed.cleaned_text,
+            fetch_limit,
+            language_filter.as_deref(),
+        )
+        .await
+        {
+            Ok(lexical_scores) => {
+                // Patterns found only by the full-text search still need a vector similarity
+                let known: HashSet<(String, String)> = initial_matches
+                    .iter()
+                    .map(|m| (m.endpoint_id.clone(), m.pattern.clone()))
+                    .collect();
+                let missing: Vec<&str> = lexical_scores
+                    .keys()
+                    .filter(|key| !known.contains(*key))
+                    .map(|(_, pattern)| pattern.as_str())
+                    .collect();
 
-    // Deduplicate matches by endpoint_id
-    let mut best_matches: HashMap<String, SearchResult> = HashMap::new();
-    for match_data in initial_matches {
-        best_matches
-            .entry(match_data.endpoint_id.clone())
-            .and_modify(|existing| {
-                if match_data.similarity > existing.similarity {
-                    *existing = match_data.clone();
+                if !missing.is_empty() {
+                    let pattern_filter = format!(
+                        "pattern IN ({})",
+                        missing
+                            .iter()
+                            .map(|p| format!("'{}'", p.replace('\'', "''")))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                    let filter = match &language_filter {
+                        Some(language_filter) => format!("{} AND {}", language_filter, pattern_filter),
+                        None => pattern_filter,
+                    };
+                    for rb in vector_search(
+                        patterns_table,
+                        query_embedding,
+                        lexical_scores.len(),
+                        Some(&filter),
+                    )
+                    .await?
+                    {
+                        let (new_matches, _similarity) =
+                            process_search_batch(rb, processed, language, config).await?;
+                        initial_matches.extend(new_matches.into_iter().filter(|m| {
+                            !known.contains(&(m.endpoint_id.clone(), m.pattern.clone()))
+                        }));
+                    }
                 }
-            })
-            .or_insert(match_data);
+
+                fuse_scores(
+                    &mut initial_matches,
+                    &lexical_scores,
+                    fusion,
+                    &config.search,
+                );
+            }
+            Err(e) => warn!(
+                "Full-text search failed, using vector similarity only (rebuild the index with --reload): {}",
+                e
+            ),
+        }
     }
 
-    // Create final sorted results
-    let mut deduplicated_matches = best_matches.into_values().collect::<Vec<_>>();
-    deduplicated_matches.sort_by(|a, b| b.similarity.partial_cmp(&a.similarity).unwrap());
+    Ok((initial_matches, rows_fetched))
+}
+
+async fn vector_search(
+    patterns_table: &Table,
+    query_embedding: &[f32],
+    limit: usize,
+    filter: Option<&str>,
+) -> AnyhowResult<Vec<RecordBatch>> {
+    let mut query = patterns_table
+        .vector_search(query_embedding)
+        .context("Failed to create vector search")?
+        .distance_type(DistanceType::Cosine)
+        .limit(limit);
+    if let Some(filter) = filter {
+        query = query.only_if(filter);
+    }
+
+    let mut results = query.execute().await?;
+    let mut batches = Vec::new();
+    while let Some(Ok(rb)) = results.next().await {
+        batches.push(rb);
+    }
+    Ok(batches)
+}
 
-    Ok((deduplicated_matches, best_similarity))
+// BM25 scores of the patterns matching `text`, keyed by (endpoint_id, pattern)
+async fn full_text_search(
+    patterns_table: &Table,
+    text: &str,
+    limit: usize,
+    filter: Option<&str>,
+) -> AnyhowResult<HashMap<(String, String), f32>> {
+    let mut query = patterns_table
+        .query()
+        .full_text_search(
+            FullTextSearchQuery::new(text.to_string()).columns(Some(vec!["pattern".to_string()])),
+        )
+        .limit(limit);
+    if let Some(filter) = filter {
+        query = query.only_if(filter);
+    }
+
+    let mut results = query.execute().await?;
+    let mut scores = HashMap::new();
+    while let Some(rb) = results.next().await {
+        let rb = rb?;
+        let endpoint_ids = string_column(&rb, "endpoint_id")?;
+        let patterns = string_column(&rb, "pattern")?;
+        let score_array = rb
+            .column_by_name("_score")
+            .ok_or_else(|| anyhow::anyhow!("Missing _score column"))?
+            .as_any()
+            .downcast_ref::<arrow::array::Float32Array>()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get _score as float"))?;
+
+        for row_idx in 0..rb.num_rows() {
+            scores.insert(
+                (
+                    endpoint_ids.value(row_idx).to_string(),
+                    patterns.value(row_idx).to_string(),
+                ),
+                score_array.value(row_idx),
+            );
+        }
+    }
+    Ok(scores)
+}
+
+fn string_column<'a>(
+    batch: &'a RecordBatch,
+    name: &str,
+) -> AnyhowResult<&'a arrow::array::StringArray> {
+    batch
+        .column_by_name(name)
+        .ok_or_else(|| anyhow::anyhow!("Missing {} column", name))?
+        .as_any()
+        .downcast_ref::<arrow::array::StringArray>()
+        .ok_or_else(|| anyhow::anyhow!("Failed to get {} as string", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RerankConfig;
+    use crate::search_operations::test_result;
+
+    #[test]
+    fn test_negative_penalty_survives_rerank() {
+        let config = Config {
+            rerank: RerankConfig {
+                enabled: true,
+                weight: 1.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let candidates = vec![
+            SearchResult {
+                is_negative: true,
+                ..test_result("send_email", "lire mes mails", 0.85)
+            },
+            test_result("send_email", "envoyer un mail à {email}", 0.80),
+            test_result("start_app", "lance {app}", 0.50),
+        ];
+
+        let (results, suppressed) =
+            score_candidates(candidates, Some(vec![0.95, 0.90, 0.70]), &config);
+
+        assert_eq!(suppressed.len(), 1);
+        let send_email = results
+            .iter()
+            .find(|r| r.endpoint_id == "send_email")
+            .unwrap();
+        let penalty = config.negatives.penalty;
+        assert!((send_email.similarity - (0.90 - penalty)).abs() < 1e-6);
+    }
 }
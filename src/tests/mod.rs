@@ -1,4 +1,5 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     // use super::*;
     use crate::{config::Config, VectorDB};
@@ -8,14 +9,15 @@ mod tests {
     const TEST_DB_PATH: &str = "data/test_db";
     const TEST_CONFIG_PATH: &str = "endpoints.yaml";
 
-    async fn setup() -> AnyhowResult<VectorDB> {
+    async fn setup() -> AnyhowResult<(VectorDB, Config)> {
         let config = Config::load_from_yaml(TEST_CONFIG_PATH)?;
-        VectorDB::new(TEST_DB_PATH, Some(config), false).await
+        let db = VectorDB::new(TEST_DB_PATH, Some(config.clone()), false).await?;
+        Ok((db, config))
     }
 
     #[tokio::test]
     async fn test_endpoint_matching() -> AnyhowResult<()> {
-        let db = setup().await?;
+        let (db, config) = setup().await?;
 
         let test_cases = vec![
             ("Run an analysis", "run analysis", 0.8),
@@ -26,7 +28,7 @@ mod tests {
         ];
 
         for (query, expected_match, min_similarity) in test_cases {
-            let (results, _similarity) = db.search_similar(query, "en", 1).await?; // Destructure here
+            let (results, _similarity) = db.search_similar(query, "en", 1, &config).await?; // Destructure here
             assert!(!results.is_empty(), "No results found for query: {}", query);
             let best_match = &results[0];
             assert!(
@@ -45,8 +47,10 @@ mod tests {
 
     #[tokio::test]
     async fn test_similar_endpoints() -> AnyhowResult<()> {
-        let db = setup().await?;
-        let (results, _similarity) = db.search_similar("Run computation", "fr", 2).await?; // Destructure here
+        let (db, config) = setup().await?;
+        let (results, _similarity) = db
+            .search_similar("Run computation", "fr", 2, &config)
+            .await?; // Destructure here
         assert!(results.len() >= 2, "Expected at least 2 results");
         // Both "run analysis" and "perform calculation" should be relatively good matches
         assert!(
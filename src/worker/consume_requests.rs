@@ -0,0 +1,377 @@
+use anyhow::{anyhow, Context, Result as AnyhowResult};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tonic::Request;
+use tracing::{error, info, warn};
+
+use crate::actions::{dispatch_action, Action, DispatchOutcome};
+use crate::config::Config;
+use crate::constants::DB_PATH;
+use crate::database::vector_db::VectorDB;
+use crate::grpc::matcher_service::matcher::matcher_server::Matcher;
+use crate::grpc::matcher_service::matcher::{MatchRequest, MatchResponse};
+use crate::grpc::matcher_service::MatcherService;
+use crate::grpc::start_grpc_server::spawn_outbox_retry;
+
+// Wait after a failed poll, doubled on each consecutive failure
+const POLL_RETRY_INITIAL: Duration = Duration::from_millis(500);
+const POLL_RETRY_MAX: Duration = Duration::from_secs(30);
+
+// Fields other producers put the query in; rejected rather than guessed so
+// that a message carrying several of them is not matched on the wrong one
+const UNSUPPORTED_QUERY_FIELDS: [&str; 2] = ["body", "text"];
+
+/// A request read from the request topic: a JSON object with the query in
+/// `query`, or plain text taken as the query itself.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MatchJob {
+    #[serde(default, alias = "request_id")]
+    pub id: Option<String>,
+    pub query: String,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+impl MatchJob {
+    pub fn parse(payload: &[u8]) -> AnyhowResult<Self> {
+        let text = std::str::from_utf8(payload)
+            .context("Request is not UTF-8")?
+            .trim();
+        let job = if text.starts_with('{') {
+            let value: serde_json::Value =
+                serde_json::from_str(text).context("Invalid JSON request")?;
+            if let Some(field) = UNSUPPORTED_QUERY_FIELDS
+                .iter()
+                .find(|field| value.get(**field).is_some())
+            {
+                return Err(anyhow!(
+                    "Unsupported field `{}`, send the query in `query`",
+                    field
+                ));
+            }
+            serde_json::from_value(value).context("Invalid JSON request")?
+        } else {
+            Self {
+                id: None,
+                query: text.to_string(),
+                language: None,
+                session_id: None,
+            }
+        };
+        if job.query.trim().is_empty() {
+            return Err(anyhow!("Request has no query"));
+        }
+        Ok(job)
+    }
+}
+
+/// Published on the response topic for every request.
+#[derive(Debug, Serialize)]
+pub struct MatchJobResult {
+    // The request's `id`, or the Iggy message id when it has none
+    pub correlation_id: String,
+    pub query: String,
+    pub language: String,
+    pub response: Option<MatchResponse>,
+    pub actions: Vec<ActionReport>,
+    // Why the request could not be matched
+    pub error: Option<String>,
+}
+
+/// What happened to the action of one intent.
+#[derive(Debug, Serialize)]
+pub struct ActionReport {
+    pub endpoint_id: String,
+    // dispatched, queued, duplicate, suppressed, incomplete or failed
+    pub status: String,
+    // The endpoint actually dispatched, differs for an inverse
+    pub dispatched_endpoint_id: Option<String>,
+    pub negated: bool,
+    pub error: Option<String>,
+}
+
+/// Worker mode: matches every request of `consumer.request_topic` and
+/// publishes the result to `consumer.response_topic`, until the stream ends.
+/// A request's offset is committed once its result is published, so requests
+/// are processed at least once.
+pub async fn consume_requests(config: Arc<Config>) -> AnyhowResult<()> {
+    let db = Arc::new(VectorDB::new(DB_PATH, None, false).await?);
+    let service = MatcherService::new(config.clone(), db);
+    if config.actions.outbox.enabled {
+        spawn_outbox_retry(config.clone());
+    }
+
+    let settings = &config.consumer;
+    let stream = settings.stream.as_deref().unwrap_or(&config.iggy.stream);
+    let pool = config.iggy_pool();
+    let mut consumer = pool
+        .consumer(&settings.group, stream, &settings.request_topic)
+        .await?;
+    info!(
+        "Consuming match requests from {}/{}, results go to {}/{}",
+        stream, settings.request_topic, stream, settings.response_topic
+    );
+
+    let mut retry_delay = POLL_RETRY_INITIAL;
+    while let Some(received) = consumer.next().await {
+        let received = match received {
+            Ok(received) => {
+                retry_delay = POLL_RETRY_INITIAL;
+                received
+            }
+            Err(e) => {
+                error!(
+                    "Failed to poll match requests, retrying in {:?}: {}",
+                    retry_delay, e
+                );
+                tokio::time::sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(POLL_RETRY_MAX);
+                continue;
+            }
+        };
+        let delivery_key = delivery_key(
+            stream,
+            &settings.request_topic,
+            received.partition_id,
+            received.message.offset,
+        );
+        let result = handle_request(
+            &service,
+            &config,
+            &received.message.payload,
+            received.message.id.to_string(),
+            &delivery_key,
+        )
+        .await;
+
+        let message = serde_json::to_string(&result)?;
+        match pool.send(stream, &settings.response_topic, &message).await {
+            Ok(()) => {
+                info!("Published result of request {}", result.correlation_id);
+                if let Err(e) = consumer
+                    .store_offset(received.message.offset, Some(received.partition_id))
+                    .await
+                {
+                    error!("Failed to commit request {}: {}", result.correlation_id, e);
+                }
+            }
+            // Left uncommitted, the request is consumed again after a restart
+            Err(e) => error!(
+                "Failed to publish result of request {}: {}",
+                result.correlation_id, e
+            ),
+        }
+    }
+    Ok(())
+}
+
+// Identifies one message of the request topic: the idempotency key of its
+// actions, so a redelivery is recognised while two requests reusing a client
+// id are not. Outbox ids only allow letters, digits, `-` and `_`.
+fn delivery_key(stream: &str, topic: &str, partition_id: u32, offset: u64) -> String {
+    let sanitize = |name: &str| -> String {
+        name.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    };
+    format!(
+        "iggy_{}_{}_p{}_o{}",
+        sanitize(stream),
+        sanitize(topic),
+        partition_id,
+        offset
+    )
+}
+
+async fn handle_request(
+    service: &MatcherService,
+    config: &Config,
+    payload: &[u8],
+    message_id: String,
+    delivery_key: &str,
+) -> MatchJobResult {
+    let job = match MatchJob::parse(payload) {
+        Ok(job) => job,
+        Err(e) => {
+            warn!("Rejected request {}: {:#}", message_id, e);
+            return MatchJobResult {
+                correlation_id: message_id,
+                query: String::new(),
+                language: config.consumer.language.clone(),
+                response: None,
+                actions: Vec::new(),
+                error: Some(format!("{:#}", e)),
+            };
+        }
+    };
+
+    let mut result = MatchJobResult {
+        correlation_id: job.id.clone().unwrap_or(message_id),
+        query: job.query.clone(),
+        language: job
+            .language
+            .clone()
+            .unwrap_or_else(|| config.consumer.language.clone()),
+        response: None,
+        actions: Vec::new(),
+        error: None,
+    };
+    let request = MatchRequest {
+        query: job.query.clone(),
+        language: result.language.clone(),
+        session_id: job.session_id.clone().unwrap_or_default(),
+        ..Default::default()
+    };
+    match service.match_query(Request::new(request)).await {
+        Ok(response) => {
+            let response = response.into_inner();
+            if config.consumer.dispatch_actions {
+                result.actions =
+                    dispatch_intents(&response, &result, &job, delivery_key, config).await;
+            }
+            result.response = Some(response);
+        }
+        Err(status) => result.error = Some(status.message().to_string()),
+    }
+    result
+}
+
+// Dispatches the best match of every intent that has all its required
+// parameters. The delivery key is the idempotency key: a request consumed
+// again after a crash does not trigger its actions twice while the outbox is
+// enabled.
+async fn dispatch_intents(
+    response: &MatchResponse,
+    result: &MatchJobResult,
+    job: &MatchJob,
+    delivery_key: &str,
+    config: &Config,
+) -> Vec<ActionReport> {
+    let mut reports = Vec::new();
+    for (index, intent) in response.intents.iter().enumerate() {
+        let Some(endpoint_match) = &intent.endpoint_match else {
+            continue;
+        };
+        let mut report = ActionReport {
+            endpoint_id: endpoint_match.endpoint_id.clone(),
+            status: String::new(),
+            dispatched_endpoint_id: None,
+            negated: endpoint_match.is_negated,
+            error: None,
+        };
+        if !endpoint_match.missing_required.is_empty() {
+            report.status = "incomplete".to_string();
+            report.error = Some(format!(
+                "Missing required parameters: {}",
+                endpoint_match
+                    .missing_required
+                    .iter()
+                    .map(|p| p.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+            reports.push(report);
+            continue;
+        }
+
+        let action = Action {
+            endpoint_id: endpoint_match.endpoint_id.clone(),
+            parameters: endpoint_match.parameters.clone(),
+            similarity: endpoint_match.similarity as f32,
+            negated: endpoint_match.is_negated,
+            query: Some(intent.clause.clone()),
+            language: Some(result.language.clone()),
+            session_id: job.session_id.clone(),
+            request_id: Some(if response.intents.len() > 1 {
+                format!("{}-{}", delivery_key, index + 1)
+            } else {
+                delivery_key.to_string()
+            }),
+        };
+        match dispatch_action(&action, config).await {
+            Ok(DispatchOutcome::Dispatched {
+                endpoint_id,
+                negated,
+            }) => {
+                report.status = "dispatched".to_string();
+                report.dispatched_endpoint_id = Some(endpoint_id);
+                report.negated = negated;
+            }
+            Ok(DispatchOutcome::Queued { endpoint_id, error }) => {
+                report.status = "queued".to_string();
+                report.dispatched_endpoint_id = Some(endpoint_id);
+                report.error = Some(error);
+            }
+            Ok(DispatchOutcome::Duplicate { endpoint_id }) => {
+                report.status = "duplicate".to_string();
+                report.dispatched_endpoint_id = Some(endpoint_id);
+            }
+            Ok(DispatchOutcome::Suppressed) => report.status = "suppressed".to_string(),
+            Err(e) => {
+                error!(
+                    "Failed to dispatch action for endpoint {}: {}",
+                    action.endpoint_id, e
+                );
+                report.status = "failed".to_string();
+                report.error = Some(e.to_string());
+            }
+        }
+        reports.push(report);
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_match_jobs() {
+        let job = MatchJob::parse(
+            br#"{"request_id": "user-001", "title": "Mail", "query": "envoyer un email"}"#,
+        )
+        .unwrap();
+        assert_eq!(job.id.as_deref(), Some("user-001"));
+        assert_eq!(job.query, "envoyer un email");
+
+        let job = MatchJob::parse(br#"{"query": "send an email", "language": "en"}"#).unwrap();
+        assert_eq!(job.id, None);
+        assert_eq!(job.language.as_deref(), Some("en"));
+
+        let job = MatchJob::parse(b"  envoyer un email\n").unwrap();
+        assert_eq!(job.query, "envoyer un email");
+
+        assert!(MatchJob::parse(b"   ").is_err());
+        assert!(MatchJob::parse(br#"{"id": "1"}"#).is_err());
+    }
+
+    #[test]
+    fn test_reject_other_query_fields() {
+        for payload in [
+            br#"{"query": "send an email", "body": "run the analysis"}"#.as_slice(),
+            br#"{"body": "envoyer un email"}"#.as_slice(),
+            br#"{"text": "envoyer un email"}"#.as_slice(),
+        ] {
+            let error = MatchJob::parse(payload).unwrap_err();
+            assert!(error.to_string().contains("send the query in `query`"));
+        }
+    }
+
+    #[test]
+    fn test_delivery_key_is_a_valid_outbox_id() {
+        assert_eq!(
+            delivery_key("gibro", "match.requests", 1, 42),
+            "iggy_gibro_match_requests_p1_o42"
+        );
+    }
+}
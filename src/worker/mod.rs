@@ -0,0 +1 @@
+pub mod consume_requests;
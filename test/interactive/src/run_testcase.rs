@@ -118,15 +118,64 @@ pub async fn run_test_case(
                         println!("Sending parameter value: {}", value);
                         tx_req_clone.send(parameter).await?;
                         parameter_index += 1;
+                    } else if !prompt.required {
+                        let skip = InteractiveRequest {
+                            request: Some(matcher::interactive_request::Request::SkipParameter(
+                                matcher::SkipParameter {
+                                    parameter_name: prompt.parameter_name.clone(),
+                                },
+                            )),
+                        };
+
+                        println!("Skipping optional parameter: {}", prompt.parameter_name);
+                        tx_req_clone.send(skip).await?;
                     }
                 }
                 matcher::interactive_response::Response::MatchResult(result) => {
                     println!("\n{}", "Received final result:".green());
                     println!("Has matches: {}", result.has_matches);
                     println!("Score: {}", result.score);
-                    if !result.matches.is_empty() {
-                        println!("Matched endpoint: {}", result.matches[0].endpoint_id);
+                    if result.matches.is_empty() {
+                        break;
                     }
+                    // The outcome of the dispatched action follows the final match
+                    println!("Matched endpoint: {}", result.matches[0].endpoint_id);
+                }
+                matcher::interactive_response::Response::ActionExecuted(executed) => {
+                    println!(
+                        "\n{}",
+                        format!("Action executed for {}", executed.endpoint_id).green()
+                    );
+                    println!("Server message: {}", executed.message);
+                    break;
+                }
+                matcher::interactive_response::Response::ActionSuppressed(suppressed) => {
+                    println!(
+                        "\n{}",
+                        format!(
+                            "Action suppressed for {}: {}",
+                            suppressed.endpoint_id, suppressed.reason
+                        )
+                        .yellow()
+                    );
+                    break;
+                }
+                matcher::interactive_response::Response::ActionFailed(failed) => {
+                    println!(
+                        "\n{}",
+                        format!(
+                            "Action failed for {}: {}",
+                            failed.endpoint_id, failed.reason
+                        )
+                        .red()
+                    );
+                    break;
+                }
+                matcher::interactive_response::Response::Cancelled(cancelled) => {
+                    println!(
+                        "\n{}",
+                        format!("Cancelled while {}", cancelled.state).yellow()
+                    );
                     break;
                 }
                 matcher::interactive_response::Response::ParameterAccepted(accepted) => {
@@ -142,6 +191,45 @@ pub async fn run_test_case(
                     // No need to send anything here - wait for next server message
                     tokio::time::sleep(Duration::from_millis(100)).await;
                 }
+                matcher::interactive_response::Response::SessionStarted(started) => {
+                    println!("Session started: {}", started.session_id);
+                }
+                matcher::interactive_response::Response::SessionResumed(resumed) => {
+                    println!("Session {} resumed ({})", resumed.session_id, resumed.state);
+                }
+                matcher::interactive_response::Response::InvalidCommand(invalid) => {
+                    println!(
+                        "{}",
+                        format!(
+                            "Server ignored {} while {}: {}",
+                            invalid.command, invalid.state, invalid.reason
+                        )
+                        .red()
+                    );
+                }
+                matcher::interactive_response::Response::ParameterRejected(rejected) => {
+                    println!(
+                        "\n{}",
+                        format!(
+                            "Parameter '{}' rejected: {}",
+                            rejected.parameter_name, rejected.reason
+                        )
+                        .red()
+                    );
+                    // The test case has no other value to offer
+                    break;
+                }
+                matcher::interactive_response::Response::SessionTimedOut(timed_out) => {
+                    println!(
+                        "\n{}",
+                        format!(
+                            "Session timed out after {}s while {}",
+                            timed_out.timeout_secs, timed_out.state
+                        )
+                        .red()
+                    );
+                    break;
+                }
             },
             _ => {
                 println!("{}", "Received empty response".red());